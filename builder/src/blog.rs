@@ -1,158 +1,761 @@
+/// `css_path` is the logical path of the post CSS in the manifest, which is emitted separately.
 pub(crate) fn asset<'a>(
     template_dir: &'a Path,
     src_dir: &'a Path,
     out_dir: &'a Path,
+    css_path: &'a str,
     templater: impl Asset<Output = Templater> + Clone + 'a,
+    site: impl Asset<Output = Rc<site::Metadata>> + Clone + 'a,
     config: impl Asset<Output = &'a Config> + Copy + 'a,
 ) -> impl Asset<Output = ()> + 'a {
     let post_template = Rc::new(
         asset::TextFile::new(template_dir.join("post.hbs"))
-            .map(|src| Template::compile(&src?).context("failed to compile blog post template"))
+            .and_then(|src| Template::compile(&src).context("failed to compile blog post template"))
             .map(Rc::new)
             .cache(),
     );
 
     let index_template = Rc::new(
         asset::TextFile::new(template_dir.join("index.hbs"))
-            .map(|src| Template::compile(&src?).context("failed to compile blog index template"))
+            .and_then(|src| {
+                Template::compile(&src).context("failed to compile blog index template")
+            })
+            .map(Rc::new)
+            .cache(),
+    );
+
+    let tag_template = Rc::new(
+        asset::TextFile::new(template_dir.join("tag.hbs"))
+            .and_then(|src| Template::compile(&src).context("failed to compile blog tag template"))
             .map(Rc::new)
             .cache(),
     );
 
     let feed_metadata = Rc::new(
         asset::TextFile::new(template_dir.join("feed.json"))
-            .map(|src| {
-                serde_json::from_str::<FeedMetadata>(&src?).context("failed to read feed.json")
+            .and_then(|src| {
+                serde_json::from_str::<FeedMetadata>(&src).context("failed to read feed.json")
             })
             .map(|res| res.map(Rc::new).map_err(|e| log::error!("{e:?}")))
             .cache(),
     );
 
-    let html = asset::Dir::new(src_dir)
-        .map(move |files| -> anyhow::Result<_> {
-            // TODO: Whenever the directory is changed at all, this entire bit of code is re-run
-            // which throws away all the old `Asset`s.
-            // That's a problem because we loes all our in-memory cache.
+    // Each post's assets are kept when the directory changes, so that their caches are too.
+    // Those of removed posts are kept for a while in case they come back,
+    // as happens when an editor saves a file by replacing it.
+    let post_sources = config
+        .map(
+            move |config_value| -> Rc<dyn Asset<Output = Vec<PostSource<'a>>> + 'a> {
+                Rc::new(
+                    asset::Dir::new(src_dir)
+                        .map(move |files| post_paths(files, config_value))
+                        .cache_each(REMOVED_POSTS_KEPT, move |(path, stem)| {
+                            let builds_page = builds_page(path, config_value);
+                            let post = post_asset(path, stem, config, config_value);
+                            (stem.clone(), builds_page, post)
+                        }),
+                )
+            },
+        )
+        .cache()
+        .flatten();
+
+    asset::all((post_sources, config))
+        .map(
+            move |(post_sources, config_value)| -> Rc<dyn Asset<Output = ()> + 'a> {
+                // Every post is read, even with `--only`, since the page still links to its
+                // neighbours.
+                let posts = post_sources.iter().map(|(_, _, post)| post.clone());
+                let posts = Rc::new(
+                    asset::all(posts.collect::<Vec<_>>())
+                        .map(process_posts)
+                        .cache(),
+                );
+
+                let mut post_pages = Vec::new();
+                for (stem, _, post) in post_sources.iter().filter(|(_, builds, _)| *builds) {
+                    let mut output_path = out_dir.join(&**stem);
+                    output_path.set_extension("html");
+
+                    // Other posts only affect this one's page through its neighbours,
+                    // so changes to them that don't change those don't re-render it.
+                    let neighbours = posts
+                        .clone()
+                        .map({
+                            let stem = stem.clone();
+                            move |posts| Neighbours::new(&posts, &stem)
+                        })
+                        .dedup();
+
+                    let post_page = asset::all((
+                        post.clone(),
+                        neighbours,
+                        templater.clone(),
+                        post_template.clone(),
+                    ))
+                    .map({
+                        let output_path = output_path.clone();
+                        move |(post, neighbours, templater, template)| {
+                            if let Some(post) = post {
+                                let built = build_post(
+                                    &post,
+                                    &neighbours,
+                                    &templater,
+                                    (*template).as_ref(),
+                                    css_path,
+                                )
+                                .unwrap_or_else(ErrorPage::into_html);
+                                write_file(config_value, &output_path, built)?;
+                                log::info!("successfully emitted {}.html", post.stem);
+                            }
+                            Ok(())
+                        }
+                    })
+                    .map(log_errors)
+                    .modifies_path(output_path);
+
+                    post_pages.push(post_page);
+                }
+
+                if let Some(only) = &config_value.only_post {
+                    if post_pages.is_empty() {
+                        log::error!("no blog post matches `{}`", only.display());
+                    }
+                    return Rc::new(asset::all(post_pages).map(|_| {}));
+                }
+
+                let feed = asset::all((posts.clone(), feed_metadata.clone(), site.clone(), config))
+                    .map(|(posts, metadata, site, config)| {
+                        let Ok(metadata) = metadata else {
+                            return Ok(());
+                        };
+                        let feed = build_feed(&posts, &metadata, &site, config.icons, &config.lang);
+                        write_file(config, out_dir.join(FEED_PATH), feed)?;
+                        log::info!("successfully emitted Atom feed");
+                        Ok(())
+                    })
+                    .map(log_errors)
+                    .modifies_path(out_dir.join(FEED_PATH));
+
+                let rss = asset::all((posts.clone(), feed_metadata.clone(), site.clone(), config))
+                    .map(|(posts, metadata, site, config)| {
+                        let Ok(metadata) = metadata else {
+                            return Ok(());
+                        };
+                        let rss = build_rss(&posts, &metadata, &site, &config.lang);
+                        write_file(config, out_dir.join(RSS_FEED_PATH), rss)?;
+                        log::info!("successfully emitted RSS feed");
+                        Ok(())
+                    })
+                    .map(log_errors)
+                    .modifies_path(out_dir.join(RSS_FEED_PATH));
+
+                let json_feed =
+                    asset::all((posts.clone(), feed_metadata.clone(), site.clone(), config))
+                        .map(|(posts, metadata, site, config)| {
+                            let Ok(metadata) = metadata else {
+                                return Ok(());
+                            };
+                            let json_feed = build_json_feed(&posts, &metadata, &site, &config.lang);
+                            write_file(config, out_dir.join(JSON_FEED_PATH), json_feed)?;
+                            log::info!("successfully emitted JSON feed");
+                            Ok(())
+                        })
+                        .map(log_errors)
+                        .modifies_path(out_dir.join(JSON_FEED_PATH));
+
+                let collections = collections(
+                    template_dir.join("collections"),
+                    out_dir,
+                    posts.clone(),
+                    templater.clone(),
+                    config,
+                );
 
+                let tags = tag_pages(
+                    out_dir,
+                    posts.clone(),
+                    templater.clone(),
+                    tag_template.clone(),
+                    config,
+                );
+
+                let index = index_pages(
+                    out_dir,
+                    posts,
+                    templater.clone(),
+                    index_template.clone(),
+                    config,
+                );
+
+                Rc::new(
+                    asset::all((
+                        asset::all(post_pages),
+                        feed,
+                        rss,
+                        json_feed,
+                        index,
+                        collections,
+                        tags,
+                    ))
+                    .map(|_| {}),
+                )
+            },
+        )
+        .cache()
+        .flatten()
+}
+
+/// The blog's pages for the sitemap, with `out_path` being where the blog is emitted
+/// relative to the output directory.
+///
+/// Only the front matter of posts is read, so this doesn't depend on rendering them.
+/// Drafts are never included, since they aren't meant to be found.
+pub(crate) fn sitemap_urls<'a>(
+    src_dir: &'a Path,
+    template_dir: &'a Path,
+    out_path: &'a str,
+    config: impl Asset<Output = &'a Config> + Copy + 'a,
+) -> impl Asset<Output = Vec<sitemap::Url>> + 'a {
+    let collections = Rc::new(
+        asset::Dir::new(template_dir.join("collections"))
+            .map(|files| {
+                // Collections are optional, and errors reading them are reported by the blog.
+                let mut names = (files.into_iter().flatten().flatten())
+                    .filter(|path| path.extension() == Some("hbs".as_ref()))
+                    .filter_map(|path| Some(path.file_stem()?.to_str()?.to_owned()))
+                    .collect::<Vec<_>>();
+                names.sort_unstable();
+                names
+            })
+            .cache(),
+    );
+
+    asset::all((asset::Dir::new(src_dir), config))
+        .map(move |(files, config_value)| -> anyhow::Result<_> {
             let mut posts = Vec::new();
-            let mut post_pages = Vec::new();
+            for path in files? {
+                let path = path?;
+                if !config_value.is_markdown(&path) {
+                    continue;
+                }
+                // The blog itself reports posts with invalid names.
+                let Some(stem) = path.file_stem().unwrap().to_str() else {
+                    continue;
+                };
+                let stem = <Rc<str>>::from(stem);
+                let post = asset::TextFile::new(path)
+                    .map(move |src| {
+                        let metadata = src
+                            .ok()
+                            .and_then(|src| Some(split_front_matter(&src).ok()?.0));
+                        Rc::new((stem.clone(), metadata))
+                    })
+                    .cache();
+                posts.push(post);
+            }
+            Ok(
+                asset::all((asset::all(posts), collections.clone(), config)).map(
+                    move |(posts, collections, config)| {
+                        let mut posts = Vec::from(posts);
+                        // Directories aren't read in any particular order.
+                        posts.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+                        blog_sitemap_urls(out_path, &posts, &collections, config)
+                    },
+                ),
+            )
+        })
+        .map(|res| -> Rc<dyn Asset<Output = _>> {
+            match res {
+                Ok(asset) => Rc::new(asset),
+                Err(e) => {
+                    log::error!("{e:?}");
+                    Rc::new(asset::Constant::new(Vec::new()))
+                }
+            }
+        })
+        .cache()
+        .flatten()
+}
+
+/// `posts` are the stems of the posts along with their metadata, or `None` if they can't be read,
+/// and `collections` are the names of the collection pages.
+fn blog_sitemap_urls(
+    out_path: &str,
+    posts: &[Rc<(Rc<str>, Option<PostMetadata>)>],
+    collections: &[String],
+    config: &Config,
+) -> Vec<sitemap::Url> {
+    let last_updated = |metadata: &PostMetadata| metadata.last_updated().max(metadata.published);
+    let url = |path: String, modified| sitemap::Url {
+        path: format!("{out_path}/{path}"),
+        modified,
+    };
+
+    // The index also lists drafts when they are built, and posts that fail to build,
+    // so count the pages the same way it does.
+    let indexed = posts
+        .iter()
+        .filter(|post| {
+            post.1
+                .as_ref()
+                .is_none_or(|metadata| config.drafts || metadata.published.is_some())
+        })
+        .count();
+    let published = posts
+        .iter()
+        .filter_map(|post| Some((&post.0, post.1.as_ref()?)))
+        .filter(|(_, metadata)| metadata.published.is_some())
+        .collect::<Vec<_>>();
+
+    let newest = published
+        .iter()
+        .filter_map(|(_, metadata)| last_updated(metadata))
+        .max();
+    let mut urls = vec![url(String::new(), newest)];
+    for page in 2..=page_count(indexed, config.posts_per_page) {
+        urls.push(url(format!("page/{page}.html"), None));
+    }
+
+    let mut tags = BTreeMap::<String, Option<NaiveDate>>::new();
+    for (stem, metadata) in published {
+        urls.push(url(stem.to_string(), last_updated(metadata)));
+        for name in &metadata.tags {
+            let slug = tag_slug(name);
+            if !slug.is_empty() {
+                let modified = tags.entry(slug).or_default();
+                *modified = (*modified).max(last_updated(metadata));
+            }
+        }
+    }
+    if !tags.is_empty() {
+        urls.push(url(format!("{TAGS_DIR}/"), newest));
+    }
+    for (slug, modified) in tags {
+        urls.push(url(format!("{TAGS_DIR}/{slug}.html"), modified));
+    }
+    for name in collections {
+        urls.push(url(format!("{name}.html"), newest));
+    }
+
+    urls
+}
+
+/// How many posts removed from the source directory to keep the assets of.
+const REMOVED_POSTS_KEPT: usize = 16;
+
+/// A post's stem, whether its page is built (see [`builds_page`]), and an asset reading it,
+/// which outputs `None` for drafts when they aren't being built.
+type PostSource<'a> = (Rc<str>, bool, Rc<dyn Asset<Output = Option<Rc<Post>>> + 'a>);
+
+/// Find the paths of the posts, along with their stems.
+fn post_paths(files: anyhow::Result<asset::DirFiles>, config: &Config) -> Vec<(PathBuf, Rc<str>)> {
+    let mut paths = Vec::new();
+    let mut stems = HashSet::new();
+
+    let files = match files {
+        Ok(files) => files,
+        Err(e) => {
+            log::error!("{e:?}");
+            return paths;
+        }
+    };
+
+    for path in files {
+        let path = match path {
+            Ok(path) => path,
+            Err(e) => {
+                log::error!("{e:?}");
+                continue;
+            }
+        };
+        if !config.is_markdown(&path) {
+            continue;
+        }
+
+        let stem = if let Some(s) = path.file_stem().unwrap().to_str() {
+            <Rc<str>>::from(s)
+        } else {
+            log::error!("filename `{}` is not valid UTF-8", path.display());
+            continue;
+        };
+
+        if !stems.insert(stem.clone()) {
+            log::error!("multiple posts named `{stem}`; ignoring {}", path.display());
+            continue;
+        }
+
+        paths.push((path, stem));
+    }
+
+    paths
+}
+
+/// Whether the page of the post at `path` is built: with [`Config::only_post`], only that post's
+/// is. Paths are canonicalized so that spellings like `./src/blog/x.md` match too.
+fn builds_page(path: &Path, config: &Config) -> bool {
+    let Some(only) = &config.only_post else {
+        return true;
+    };
+    path.file_name() == Some(only.as_os_str())
+        || fs::canonicalize(only)
+            .is_ok_and(|only| fs::canonicalize(path).is_ok_and(|path| path == only))
+}
+
+fn post_asset<'a>(
+    path: &Path,
+    stem: &Rc<str>,
+    config: impl Asset<Output = &'a Config> + 'a,
+    config_value: &'a Config,
+) -> Rc<dyn Asset<Output = Option<Rc<Post>>> + 'a> {
+    // The config is part of what identifies entries in the disk cache, so the
+    // rendered Markdown needn't depend on the time it was created.
+    let rendered = asset::TextFile::new(path.to_owned())
+        .par_map({
+            let path = path.to_owned();
+            let options = config_value.markdown.clone();
+            move |src| Some(render_post(&path, &src.ok()?, &options))
+        })
+        .map(|rendered| rendered.map(Rc::new))
+        .persist_cache(format!("blog post {}", path.display()));
+
+    let post = asset::all((
+        asset::TextFile::new(path.to_owned()).with_modified(),
+        rendered,
+    ))
+    .map({
+        let path = path.to_owned();
+        let stem = stem.clone();
+        move |((modified, src), rendered)| {
+            Rc::new(read_post(stem.clone(), &path, src, modified, rendered))
+        }
+    })
+    .cache();
+
+    Rc::new(
+        asset::all((config, post))
+            .map(move |(config, post)| (config.drafts || !post.is_draft()).then_some(post)),
+    )
+}
+
+/// The name of the post CSS, relative to the blog.
+pub(crate) const POST_CSS_PATH: &str = "post.css";
+
+/// Pages listing the posts in a custom way, such as an archive.
+/// Each `NAME.hbs` template in the collections directory is rendered to `NAME.html`
+/// with the same variables as the blog index.
+fn collections<'a>(
+    dir: PathBuf,
+    out_dir: &'a Path,
+    posts: impl Asset<Output = Rc<Vec<Rc<Post>>>> + Clone + 'a,
+    templater: impl Asset<Output = Templater> + Clone + 'a,
+    config: impl Asset<Output = &'a Config> + Copy + 'a,
+) -> impl Asset<Output = ()> + 'a {
+    asset::Dir::new(dir.clone())
+        .map(move |files| -> anyhow::Result<_> {
+            let mut pages = Vec::new();
+
+            // Collections are optional.
+            if !dir.exists() {
+                return Ok(asset::all(pages));
+            }
 
             for path in files? {
                 let path = path?;
-                if path.extension() != Some("md".as_ref()) {
+                if path.extension() != Some("hbs".as_ref()) {
                     continue;
                 }
 
-                let stem = if let Some(s) = path.file_stem().unwrap().to_str() {
-                    <Rc<str>>::from(s)
-                } else {
+                let Some(name) = path.file_stem().unwrap().to_str() else {
                     log::error!("filename `{}` is not valid UTF-8", path.display());
                     continue;
                 };
+                let name = <Rc<str>>::from(name);
+                let output_path = out_dir.join(format!("{name}.html"));
 
-                let mut output_path = out_dir.join(&*stem);
-                output_path.set_extension("html");
-
-                let post = asset::TextFile::new(path)
-                    .map(move |src| Rc::new(read_post(stem.clone(), src)))
+                let template = asset::TextFile::new(path)
+                    .and_then({
+                        let name = name.clone();
+                        move |src| {
+                            Template::compile(&src)
+                                .with_context(|| format!("failed to compile collection {name}"))
+                        }
+                    })
+                    .map(Rc::new)
                     .cache();
 
-                let post = Rc::new(asset::all((config, post)).map(move |(config, post)| {
-                    (config.drafts || !post.is_draft()).then_some(post)
-                }));
-
-                posts.push(post.clone());
-
-                let post_page = asset::all((post, templater.clone(), post_template.clone()))
+                let page = asset::all((posts.clone(), templater.clone(), template, config))
                     .map({
                         let output_path = output_path.clone();
-                        move |(post, templater, template)| {
-                            if let Some(post) = post {
-                                let built = build_post(&post, &templater, (*template).as_ref())
-                                    .unwrap_or_else(ErrorPage::into_html);
-                                write_file(&output_path, built)?;
-                                log::info!("successfully emitted {}.html", post.stem);
-                            }
+                        move |(posts, templater, template, config)| {
+                            let page = build_listing(
+                                &posts,
+                                &PageLinks::default(),
+                                (),
+                                &templater,
+                                &template,
+                            )
+                            .unwrap_or_else(ErrorPage::into_html);
+                            write_file(config, &output_path, page)?;
+                            log::info!("successfully emitted {name}.html");
                             Ok(())
                         }
                     })
                     .map(log_errors)
                     .modifies_path(output_path);
 
-                post_pages.push(post_page);
+                pages.push(page);
             }
 
-            let posts = Rc::new(asset::all(posts).map(process_posts).cache());
-
-            let feed = asset::all((posts.clone(), feed_metadata.clone()))
-                .map(|(posts, metadata)| {
-                    let Ok(metadata) = metadata else {
-                        return Ok(());
-                    };
-                    let feed = build_feed(&posts, &metadata);
-                    write_file(out_dir.join(FEED_PATH), feed)?;
-                    log::info!("successfully emitted Atom feed");
-                    Ok(())
-                })
-                .map(log_errors)
-                .modifies_path(out_dir.join(FEED_PATH));
-
-            let index = asset::all((posts, templater.clone(), index_template.clone()))
-                .map(|(posts, templater, template)| {
-                    let index = build_index(&posts, &templater, &template)
-                        .unwrap_or_else(ErrorPage::into_html);
-                    write_file(out_dir.join("index.html"), index)?;
-                    log::info!("successfully emitted blog index");
-                    Ok(())
-                })
-                .map(log_errors)
-                .modifies_path(out_dir.join("index.html"));
-
-            Ok(asset::all((asset::all(post_pages), feed, index)).map(|_| {}))
+            Ok(asset::all(pages))
         })
         .map(|res| -> Rc<dyn Asset<Output = _>> {
             match res {
-                Ok(asset) => Rc::new(asset),
+                Ok(asset) => Rc::new(asset.map(|_| {})),
                 Err(e) => {
-                    log::error!("{:?}", e);
+                    log::error!("{e:?}");
                     Rc::new(asset::Constant::new(()))
                 }
             }
         })
         .cache()
-        .flatten();
+        .flatten()
+}
 
-    let post_css = asset::TextFile::new(template_dir.join("post.css")).map(|res| {
-        res.unwrap_or_else(|e| {
-            log::error!("{e:?}");
-            String::new()
-        })
-    });
+/// The pages of the blog index, each emitted separately so that they are all output paths.
+/// Pages left over from when there were more are removed.
+fn index_pages<'a>(
+    out_dir: &'a Path,
+    posts: impl Asset<Output = Rc<Vec<Rc<Post>>>> + 'a,
+    templater: impl Asset<Output = Templater> + 'a,
+    template: impl Asset<Output = Rc<anyhow::Result<Template>>> + 'a,
+    config: impl Asset<Output = &'a Config> + 'a,
+) -> impl Asset<Output = ()> + 'a {
+    asset::all((posts, templater, template, config))
+        .with_modified()
+        .map(
+            move |(modified, (posts, templater, template, config))| -> Rc<dyn Asset<Output = ()> + 'a> {
+                let per_page = config.posts_per_page;
+                let pages = page_count(posts.len(), per_page);
+                log_errors(remove_stale_files(config, &out_dir.join(INDEX_PAGES_DIR), |name| {
+                    let page = name.strip_suffix(".html").and_then(|page| page.parse().ok());
+                    page.is_some_and(|page: usize| page > pages)
+                }));
+
+                let page_assets = (1..=pages).map(|i| {
+                    let path = out_dir.join(index_page_path(i));
+                    let (posts, templater, template) =
+                        (posts.clone(), templater.clone(), template.clone());
+                    asset::Dynamic::modified_at((), modified)
+                        .map({
+                            let path = path.clone();
+                            move |()| {
+                                let page_posts = page_slices(&posts, per_page).nth(i - 1).unwrap();
+                                let page = IndexPage {
+                                    root: if i == 1 { "" } else { "../" },
+                                    page: i,
+                                    pages,
+                                };
+                                let html = build_listing(
+                                    page_posts,
+                                    &page.links(),
+                                    page,
+                                    &templater,
+                                    &template,
+                                )
+                                .unwrap_or_else(ErrorPage::into_html);
+                                write_file(config, &path, html)?;
+                                log::info!("successfully emitted blog index page {i} of {pages}");
+                                Ok(())
+                            }
+                        })
+                        .map(log_errors)
+                        .modifies_path(path)
+                });
+                Rc::new(asset::all(page_assets.collect::<Vec<_>>()).map(|_| {}))
+            },
+        )
+        .cache()
+        .flatten()
+}
+
+/// The directory of the blog index's pages after the first.
+const INDEX_PAGES_DIR: &str = "page";
+
+const TAGS_DIR: &str = "tags";
 
-    let code_themes_dir = template_dir.join("code_themes");
-    let dark_theme = theme_asset(code_themes_dir.join("dark.tmTheme"));
-    let light_theme = theme_asset(code_themes_dir.join("light.tmTheme"));
-
-    let css = asset::all((post_css, light_theme, dark_theme, config))
-        .map(|(mut post_css, light_theme, dark_theme, config)| {
-            post_css.push_str(&dark_theme);
-            post_css.push_str("@media(prefers-color-scheme:light){");
-            post_css.push_str(&light_theme);
-            post_css.push('}');
-            if config.minify {
-                minify(minify::FileType::Css, &mut post_css);
+/// A page for each tag listing the posts with it at `tags/SLUG.html`,
+/// and an overview of every tag at `tags/index.html`, all rendered from `tag.hbs`
+/// with the same variables as the blog index.
+fn tag_pages<'a>(
+    out_dir: &'a Path,
+    posts: impl Asset<Output = Rc<Vec<Rc<Post>>>> + 'a,
+    templater: impl Asset<Output = Templater> + 'a,
+    template: impl Asset<Output = Rc<anyhow::Result<Template>>> + 'a,
+    config: impl Asset<Output = &'a Config> + 'a,
+) -> impl Asset<Output = ()> + 'a {
+    let tags_dir = out_dir.join(TAGS_DIR);
+
+    asset::all((posts, templater, template, config))
+        .with_modified()
+        .map(
+            move |(modified, (posts, templater, template, config))| -> Rc<dyn Asset<Output = ()> + 'a> {
+                let tags = collect_tags(&posts);
+                // Pages of tags no longer in any post are removed,
+                // and so is the overview when there are none.
+                let slugs = tags.iter().map(|tag| &*tag.slug).collect::<HashSet<_>>();
+                log_errors(remove_stale_files(config, &tags_dir, |name| {
+                    name.strip_suffix(".html").is_some_and(|stem| {
+                        tags.is_empty() || (stem != "index" && !slugs.contains(stem))
+                    })
+                }));
+                // The template is optional when no post has tags.
+                if tags.is_empty() {
+                    return Rc::new(asset::Constant::new(()));
+                }
+
+                let summaries = Rc::new(tags.iter().map(TagSummary::new).collect::<Vec<_>>());
+                // Each tag's page, followed by the overview.
+                let pages = tags
+                    .iter()
+                    .enumerate()
+                    .map(|(i, tag)| (Some(i), format!("{}.html", tag.slug), tag.posts.clone()))
+                    .chain([(None, "index.html".to_owned(), posts.to_vec())]);
+                let page_assets = pages.map(|(i, file_name, listed)| {
+                    let path = tags_dir.join(file_name);
+                    let (summaries, templater, template) =
+                        (summaries.clone(), templater.clone(), template.clone());
+                    asset::Dynamic::modified_at((), modified)
+                        .map({
+                            let path = path.clone();
+                            move |()| {
+                                let vars = TagVars {
+                                    root: "../",
+                                    tag: i.map(|i| &summaries[i]),
+                                    tags: &summaries,
+                                };
+                                let page = build_listing(
+                                    &listed,
+                                    &PageLinks::default(),
+                                    vars,
+                                    &templater,
+                                    &template,
+                                )
+                                .unwrap_or_else(ErrorPage::into_html);
+                                write_file(config, &path, page)?;
+                                log::info!("successfully emitted {}", path.display());
+                                Ok(())
+                            }
+                        })
+                        .map(log_errors)
+                        .modifies_path(path)
+                });
+                Rc::new(asset::all(page_assets.collect::<Vec<_>>()).map(|_| {}))
+            },
+        )
+        .cache()
+        .flatten()
+}
+
+struct Tag<'a> {
+    /// The tag as first written in a post.
+    name: &'a str,
+    slug: String,
+    /// Every post with the tag, in the same order as the index.
+    posts: Vec<Rc<Post>>,
+}
+
+/// Group posts by the slugs of their tags, sorted by slug.
+fn collect_tags(posts: &[Rc<Post>]) -> Vec<Tag<'_>> {
+    let mut tags = BTreeMap::<String, Tag<'_>>::new();
+    // Differently-written tags that have been warned about already.
+    let mut collisions = HashSet::new();
+
+    for post in posts {
+        let Ok(content) = &post.content else {
+            continue;
+        };
+        for name in &content.metadata.tags {
+            let slug = tag_slug(name);
+            if slug.is_empty() {
+                log::warn!(
+                    "tag `{name}` of post {} has no letters or digits",
+                    post.stem
+                );
+                continue;
             }
-            write_file(out_dir.join(POST_CSS_PATH), post_css)?;
-            log::info!("successfully emitted post CSS");
-            Ok(())
-        })
-        .map(log_errors)
-        .modifies_path(out_dir.join(POST_CSS_PATH));
+            let tag = tags.entry(slug.clone()).or_insert_with(|| Tag {
+                name,
+                slug,
+                posts: Vec::new(),
+            });
+            if tag.name != name && collisions.insert(name) {
+                log::warn!(
+                    "tags `{}` and `{name}` both have the slug `{}`; they will share a page",
+                    tag.name,
+                    tag.slug,
+                );
+            }
+            // Don't list a post twice if it has the same tag twice.
+            if !tag.posts.last().is_some_and(|last| Rc::ptr_eq(last, post)) {
+                tag.posts.push(post.clone());
+            }
+        }
+    }
 
-    asset::all((html, css)).map(|((), ())| {})
+    tags.into_values().collect()
+}
+
+/// The name of a tag's page: lowercase letters and digits separated by single hyphens.
+/// Letters outside ASCII are kept, so links to the page must be escaped with [`tag_href`].
+fn tag_slug(name: &str) -> String {
+    let mut slug = String::new();
+    for word in name.split(|c: char| !c.is_alphanumeric()) {
+        if word.is_empty() {
+            continue;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(&word.to_lowercase());
+    }
+    slug
+}
+
+/// The URL of a tag's page relative to the tags directory.
+fn tag_href(slug: &str) -> String {
+    let mut href = String::new();
+    escape_href(&mut href, slug);
+    href.push_str(".html");
+    href
+}
+
+/// Variables of tag pages on top of those of the blog index.
+#[derive(Serialize)]
+struct TagVars<'a> {
+    /// The path from the page to the blog's directory.
+    root: &'static str,
+    /// The tag whose posts are listed, or `None` on the overview of every tag.
+    tag: Option<&'a TagSummary>,
+    tags: &'a [TagSummary],
+}
+
+#[derive(Serialize)]
+struct TagSummary {
+    name: String,
+    href: String,
+    count: usize,
 }
 
-const POST_CSS_PATH: &str = "post.css";
+impl TagSummary {
+    fn new(tag: &Tag<'_>) -> Self {
+        Self {
+            name: tag.name.to_owned(),
+            href: tag_href(&tag.slug),
+            count: tag.posts.len(),
+        }
+    }
+}
 
 // Serialization used in the templates
 #[derive(Serialize)]
@@ -166,6 +769,10 @@ struct Post {
 }
 
 impl Post {
+    fn published(&self) -> Option<NaiveDate> {
+        self.content.as_ref().ok()?.metadata.published
+    }
+
     fn is_draft(&self) -> bool {
         self.content
             .as_ref()
@@ -177,32 +784,171 @@ impl Post {
 struct PostContent {
     metadata: PostMetadata,
     markdown: Markdown,
+    /// Roughly how many minutes the post takes to read.
+    reading_time: usize,
+    /// When the post's source file was last modified.
+    #[serde(skip)]
+    source_modified: Option<DateTime<FixedOffset>>,
 }
 
 #[derive(Default, Serialize, Deserialize)]
 struct PostMetadata {
     published: Option<NaiveDate>,
     updated: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    revisions: Vec<Revision>,
+    /// A title to use instead of the one given by the Markdown's top-level heading,
+    /// for example when the post has none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    /// Tags the post is listed under, each with a page at `tags/SLUG.html`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    /// The name of a code theme to use instead of the default light and dark ones.
+    code_theme: Option<String>,
+    /// Markdown extensions to enable or disable for this post only,
+    /// e.g. `"markdown": { "smart-punctuation": false }`.
+    #[serde(default)]
+    markdown: markdown::FeatureOverrides,
+}
+
+impl PostMetadata {
+    /// The date of the most recent update, either from `updated` or the revision history.
+    fn last_updated(&self) -> Option<NaiveDate> {
+        let revisions = self.revisions.iter().map(|revision| revision.date);
+        self.updated.into_iter().chain(revisions).max()
+    }
+}
+
+/// An entry in a post's revision history.
+#[derive(Serialize, Deserialize)]
+struct Revision {
+    date: NaiveDate,
+    note: String,
+}
+
+/// A post's rendered Markdown, which is the slow part of reading a post
+/// and so is cached on disk between runs.
+#[derive(Serialize, Deserialize)]
+struct RenderedPost {
+    markdown: Markdown,
+    /// Kept separately since `Markdown` only serializes what templates use.
+    diagnostics: Vec<markdown::Diagnostic>,
+}
+
+fn render_post(path: &Path, src: &str, options: &markdown::Options) -> RenderedPost {
+    // Invalid front matter is reported by `read_post`.
+    let (metadata, markdown) =
+        split_front_matter(src).unwrap_or_else(|_| (PostMetadata::default(), src));
+    let options = markdown::Options {
+        features: options.features.with_overrides(metadata.markdown),
+        ..options.clone()
+    };
+
+    log::info!("rendering {}", path.display());
+    let _context = PanicContext(format!("parsing {}", path.display()));
+    let mut markdown = markdown::parse(markdown, &options);
+    let diagnostics = mem::take(&mut markdown.diagnostics);
+    RenderedPost {
+        markdown,
+        diagnostics,
+    }
 }
 
-fn read_post(stem: Rc<str>, src: anyhow::Result<String>) -> Post {
+fn read_post(
+    stem: Rc<str>,
+    path: &Path,
+    src: anyhow::Result<String>,
+    modified: asset::Modified,
+    rendered: Option<Rc<RenderedPost>>,
+) -> Post {
     Post {
-        content: src.map(|src| {
-            let mut json = serde_json::Deserializer::from_str(&src).into_iter();
-            let metadata = json.next().and_then(Result::ok).unwrap_or_default();
-            let markdown = &src[json.byte_offset()..];
-
-            let mut markdown = markdown::parse(markdown);
-            if markdown.title.is_empty() {
-                log::warn!("Post in {stem}.md does not have title");
-                markdown.title = format!("Untitled post from {stem}.md");
+        content: src.and_then(|src| {
+            let (metadata, markdown) = split_front_matter(&src)?;
+            let front_matter = &src[..src.len() - markdown.len()];
+
+            let rendered = rendered.context("post was removed while being read")?;
+            let mut markdown = rendered.markdown.clone();
+            markdown.diagnostics.clone_from(&rendered.diagnostics);
+            markdown.log_diagnostics(path, front_matter.matches('\n').count());
+            if let Some(title) = &metadata.title {
+                // The title is otherwise HTML, while front matter is plain text.
+                markdown.title.clear();
+                escape_html(&mut markdown.title, title);
+                markdown.title_text.clone_from(title);
+            } else if markdown.title.is_empty() {
+                log::warn!("Post in {} does not have title", path.display());
+                markdown.title_text = format!("Untitled post from {stem}");
+                markdown.title.clear();
+                escape_html(&mut markdown.title, &markdown.title_text);
             }
-            PostContent { metadata, markdown }
+            let source_modified = match modified {
+                asset::Modified::Never => None,
+                asset::Modified::At(time) => Some(DateTime::<Utc>::from(time).into()),
+            };
+            Ok(PostContent {
+                metadata,
+                reading_time: reading_time(&markdown.body),
+                markdown,
+                source_modified,
+            })
         }),
         stem,
     }
 }
 
+/// Split a post's source into its JSON front matter, if it has any, and its Markdown.
+fn split_front_matter(src: &str) -> anyhow::Result<(PostMetadata, &str)> {
+    let mut json = serde_json::Deserializer::from_str(src).into_iter();
+    match json.next() {
+        Some(Ok(metadata)) => Ok((metadata, &src[json.byte_offset()..])),
+        // Front matter is an object, so a source starting with anything else has none.
+        Some(Err(e)) if src.trim_start().starts_with('{') => {
+            Err(e).context("failed to parse front matter")
+        }
+        _ => Ok((PostMetadata::default(), src)),
+    }
+}
+
+/// Estimate how many minutes it takes to read a post from its rendered HTML,
+/// not counting code, which isn't read at the same pace as prose.
+fn reading_time(html: &str) -> usize {
+    const WORDS_PER_MINUTE: usize = 220;
+
+    let mut words = 0;
+    // How many elements whose text shouldn't be counted we are in.
+    let mut skipping = 0_usize;
+    let mut rest = html;
+    loop {
+        let (text, tag) = match rest.split_once('<') {
+            Some((text, after)) => (text, after),
+            None => (rest, ""),
+        };
+        if skipping == 0 {
+            words += text.split_whitespace().count();
+        }
+        if tag.is_empty() {
+            break;
+        }
+        let (tag, after) = tag.split_once('>').unwrap_or((tag, ""));
+        let (closing, tag) = match tag.strip_prefix('/') {
+            Some(tag) => (true, tag),
+            None => (false, tag),
+        };
+        let name = tag.split_whitespace().next().unwrap_or("");
+        if ["pre", "code", "style", "script"].contains(&name) {
+            if closing {
+                skipping = skipping.saturating_sub(1);
+            } else {
+                skipping += 1;
+            }
+        }
+        rest = after;
+    }
+
+    words.div_ceil(WORDS_PER_MINUTE).max(1)
+}
+
 fn process_posts(posts: Box<[Option<Rc<Post>>]>) -> Rc<Vec<Rc<Post>>> {
     // Remove disabled posts: drafts when they are disabled
     let mut posts: Vec<_> = Vec::from(posts).into_iter().flatten().collect();
@@ -230,20 +976,141 @@ fn process_posts(posts: Box<[Option<Rc<Post>>]>) -> Rc<Vec<Rc<Post>>> {
 
 #[derive(Deserialize)]
 struct FeedMetadata {
-    site: String,
+    /// The URL of the site the blog is part of; defaults to the site metadata's.
+    #[serde(default)]
+    site: Option<String>,
     url: String,
     title: String,
+    /// The name of the blog's author; defaults to the site metadata's.
+    #[serde(default)]
+    name: Option<String>,
+    /// Whether to use the modification time of a post's source file as its updated time in the
+    /// feed when it doesn't specify one explicitly.
+    ///
+    /// Note that checking out the repository resets these times,
+    /// so this is only useful if the builder is run somewhere they are preserved.
+    #[serde(default)]
+    feed_mtime_updated: bool,
+    /// If set, entries are identified by [tag URIs] minted by this authority (e.g. a domain name
+    /// or email address that the author owned on the post's publication date) instead of by
+    /// their URLs, so that their IDs stay the same if the site moves.
+    ///
+    /// [tag URIs]: https://www.rfc-editor.org/rfc/rfc4151
+    #[serde(default)]
+    tag_authority: Option<String>,
+    /// The software advertised as generating the feed; defaults to the site itself.
+    #[serde(default)]
+    generator: Option<FeedGenerator>,
+    /// How many of the latest posts the feed contains, with `0` or `"all"` meaning every post.
+    /// Defaults to 10.
+    #[serde(
+        default = "default_feed_entries",
+        deserialize_with = "deserialize_feed_entries",
+        alias = "feed_length"
+    )]
+    feed_entries: Option<usize>,
+}
+
+#[allow(clippy::unnecessary_wraps)] // It must return the field's type.
+fn default_feed_entries() -> Option<usize> {
+    Some(10)
+}
+
+fn deserialize_feed_entries<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<usize>, D::Error> {
+    struct Visitor;
+    impl de::Visitor<'_> for Visitor {
+        type Value = Option<usize>;
+        fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            f.write_str("a number of entries or \"all\"")
+        }
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+            let v =
+                usize::try_from(v).map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &self))?;
+            Ok((v != 0).then_some(v))
+        }
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+            match v {
+                "all" => Ok(None),
+                _ => Err(E::invalid_value(Unexpected::Str(v), &self)),
+            }
+        }
+    }
+    deserializer.deserialize_any(Visitor)
+}
+
+#[derive(Deserialize)]
+struct FeedGenerator {
     name: String,
+    uri: Option<String>,
 }
 
 const FEED_PATH: &str = "feed.xml";
+const RSS_FEED_PATH: &str = "feed_rss.xml";
+const JSON_FEED_PATH: &str = "feed.json";
 
-fn build_feed(posts: &[Rc<Post>], metadata: &FeedMetadata) -> String {
-    fn datetime(date: NaiveDate) -> DateTime<chrono::offset::FixedOffset> {
-        chrono::offset::Utc
-            .from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
-            .into()
-    }
+fn datetime(date: NaiveDate) -> DateTime<FixedOffset> {
+    Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+        .into()
+}
+
+/// A post as an entry of the feeds.
+struct FeedEntry<'a> {
+    content: &'a PostContent,
+    url: String,
+    id: String,
+    published: DateTime<FixedOffset>,
+    updated: DateTime<FixedOffset>,
+}
+
+/// The entries of the feeds: the latest published posts, up to `feed_entries` of them.
+fn feed_entries<'a>(
+    posts: &'a [Rc<Post>],
+    metadata: &'a FeedMetadata,
+) -> impl Iterator<Item = FeedEntry<'a>> {
+    let limit = metadata.feed_entries.unwrap_or(usize::MAX);
+    posts
+        .iter()
+        .filter_map(|post| {
+            let content = post.content.as_ref().ok()?;
+            let published_date = content.metadata.published?;
+            let published = datetime(published_date);
+
+            let updated = match content.metadata.last_updated() {
+                Some(updated) => datetime(updated),
+                None if metadata.feed_mtime_updated => content
+                    .source_modified
+                    .map_or(published, |modified| modified.max(published)),
+                None => published,
+            };
+
+            let url = format!("{}{}", metadata.url, post.stem);
+            let id = match &metadata.tag_authority {
+                Some(authority) => format!("tag:{authority},{published_date}:{}", post.stem),
+                None => url.clone(),
+            };
+
+            Some(FeedEntry {
+                content,
+                url,
+                id,
+                published,
+                updated,
+            })
+        })
+        .take(limit)
+}
+
+fn build_feed(
+    posts: &[Rc<Post>],
+    metadata: &FeedMetadata,
+    site: &site::Metadata,
+    icons: bool,
+    lang: &str,
+) -> String {
+    let site_url = metadata.site.as_deref().unwrap_or(&site.url);
+    let author = metadata.name.as_deref().unwrap_or(&site.author);
 
     let mut feed = atom_syndication::FeedBuilder::default();
 
@@ -261,24 +1128,29 @@ fn build_feed(posts: &[Rc<Post>], metadata: &FeedMetadata) -> String {
 
     feed.author(
         atom_syndication::PersonBuilder::default()
-            .name(metadata.name.clone())
-            .uri(Some(metadata.site.clone()))
+            .name(author.to_owned())
+            .uri(Some(site_url.to_owned()))
             .build(),
     );
 
+    let (generator_name, generator_uri) = match &metadata.generator {
+        Some(generator) => (generator.name.clone(), generator.uri.clone()),
+        None => (site_url.to_owned(), Some(site_url.to_owned())),
+    };
     let generator = atom_syndication::GeneratorBuilder::default()
-        .value("sabrinajewson.github.io".to_owned())
-        .uri(Some(
-            "https://github.com/SabrinaJewson/sabrinajewson.github.io".to_owned(),
-        ))
+        .value(generator_name)
+        .uri(generator_uri)
         .build();
     feed.generator(Some(generator));
 
-    feed.icon(Some(format!(
-        "{}/{}",
-        metadata.site,
-        crate::icons::PATHS.apple_touch_icon
-    )));
+    // Without icons, the icon URL would be broken
+    if icons {
+        feed.icon(Some(format!(
+            "{}/{}",
+            site_url,
+            crate::icons::PATHS.apple_touch_icon
+        )));
+    }
 
     // self-link
     feed.link(
@@ -298,31 +1170,25 @@ fn build_feed(posts: &[Rc<Post>], metadata: &FeedMetadata) -> String {
             .build(),
     );
 
-    for post in posts.iter().take(10) {
-        let Ok(content) = &post.content else { continue };
-        let Some(published) = content.metadata.published.map(datetime) else {
-            continue;
-        };
-
-        let post_url = format!("{}{}", metadata.url, post.stem);
-
+    for entry in feed_entries(posts, metadata) {
+        let markdown = &entry.content.markdown;
         feed.entry(
             atom_syndication::EntryBuilder::default()
-                .title(&*content.markdown.title)
-                .id(post_url.clone())
+                .title(&*markdown.title_text)
+                .id(entry.id)
                 .link(
                     atom_syndication::LinkBuilder::default()
-                        .href(post_url.clone())
+                        .href(entry.url.clone())
                         .mime_type(Some("text/html".to_owned()))
-                        .title(Some(content.markdown.title.clone()))
+                        .title(Some(markdown.title_text.clone()))
                         .build(),
                 )
-                .published(published)
-                .updated(content.metadata.updated.map_or(published, datetime))
+                .published(entry.published)
+                .updated(entry.updated)
                 .content(
                     atom_syndication::ContentBuilder::default()
-                        .base(Some(post_url))
-                        .value(Some(content.markdown.body.clone()))
+                        .base(Some(entry.url))
+                        .value(Some(markdown.body.clone()))
                         .content_type(Some("html".to_owned()))
                         .build(),
                 )
@@ -330,64 +1196,340 @@ fn build_feed(posts: &[Rc<Post>], metadata: &FeedMetadata) -> String {
         );
     }
 
-    feed.lang(Some("en".to_owned()));
+    feed.lang(Some(lang.to_owned()));
 
     feed.build().to_string()
 }
 
-fn build_index(
+/// Build an RSS 2.0 feed with the same entries as the Atom one,
+/// for readers that handle RSS better.
+fn build_rss(
     posts: &[Rc<Post>],
+    metadata: &FeedMetadata,
+    site: &site::Metadata,
+    lang: &str,
+) -> String {
+    let site_url = metadata.site.as_deref().unwrap_or(&site.url);
+
+    let mut rss = String::from("<?xml version='1.0' encoding='utf-8'?>");
+    rss.push_str("<rss version='2.0' xmlns:atom='http://www.w3.org/2005/Atom'><channel>");
+
+    rss.push_str("<title>");
+    escape_html(&mut rss, &metadata.title);
+    rss.push_str("</title><link>");
+    escape_html(&mut rss, &metadata.url);
+    rss.push_str("</link><description>");
+    escape_html(&mut rss, &metadata.title);
+    rss.push_str("</description><language>");
+    escape_html(&mut rss, lang);
+    rss.push_str("</language><generator>");
+    let generator = metadata
+        .generator
+        .as_ref()
+        .map_or(site_url, |generator| &generator.name);
+    escape_html(&mut rss, generator);
+    rss.push_str("</generator>");
+
+    // self-link
+    rss.push_str("<atom:link rel='self' type='application/rss+xml' href='");
+    escape_html(&mut rss, &format!("{}{RSS_FEED_PATH}", metadata.url));
+    rss.push_str("'/>");
+
+    // Last built is the date of the latest post or update
+    if let Some(updated) = posts
+        .iter()
+        .filter_map(|post| {
+            let metadata = &post.content.as_ref().ok()?.metadata;
+            let published = metadata.published?;
+            Some(
+                metadata
+                    .last_updated()
+                    .map_or(published, |updated| updated.max(published)),
+            )
+        })
+        .max()
+    {
+        push!(
+            rss,
+            "<lastBuildDate>{}</lastBuildDate>",
+            datetime(updated).to_rfc2822()
+        );
+    }
+
+    for entry in feed_entries(posts, metadata) {
+        rss.push_str("<item><title>");
+        escape_html(&mut rss, &entry.content.markdown.title_text);
+        rss.push_str("</title><link>");
+        escape_html(&mut rss, &entry.url);
+        rss.push_str("</link>");
+        if entry.id == entry.url {
+            rss.push_str("<guid>");
+        } else {
+            rss.push_str("<guid isPermaLink='false'>");
+        }
+        escape_html(&mut rss, &entry.id);
+        rss.push_str("</guid>");
+        // RSS has no separate updated date, so entries are dated by when they were published.
+        push!(rss, "<pubDate>{}</pubDate>", entry.published.to_rfc2822());
+        rss.push_str("<description>");
+        escape_html(&mut rss, &entry.content.markdown.body);
+        rss.push_str("</description></item>");
+    }
+
+    rss.push_str("</channel></rss>");
+    rss
+}
+
+/// Build a [JSON Feed] with the same entries as the Atom one.
+///
+/// [JSON Feed]: https://www.jsonfeed.org/version/1.1/
+fn build_json_feed(
+    posts: &[Rc<Post>],
+    metadata: &FeedMetadata,
+    site: &site::Metadata,
+    lang: &str,
+) -> String {
+    #[derive(Serialize)]
+    struct JsonFeed<'a> {
+        version: &'static str,
+        title: &'a str,
+        home_page_url: &'a str,
+        feed_url: String,
+        authors: [Author<'a>; 1],
+        language: &'a str,
+        items: Vec<Item<'a>>,
+    }
+    #[derive(Serialize)]
+    struct Author<'a> {
+        name: &'a str,
+        url: &'a str,
+    }
+    #[derive(Serialize)]
+    struct Item<'a> {
+        id: String,
+        url: String,
+        title: &'a str,
+        content_html: &'a str,
+        date_published: String,
+        date_modified: String,
+    }
+
+    let feed = JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title: &metadata.title,
+        home_page_url: &metadata.url,
+        feed_url: format!("{}{JSON_FEED_PATH}", metadata.url),
+        authors: [Author {
+            name: metadata.name.as_deref().unwrap_or(&site.author),
+            url: metadata.site.as_deref().unwrap_or(&site.url),
+        }],
+        language: lang,
+        items: feed_entries(posts, metadata)
+            .map(|entry| Item {
+                id: entry.id,
+                url: entry.url,
+                title: &entry.content.markdown.title_text,
+                content_html: &entry.content.markdown.body,
+                date_published: entry.published.to_rfc3339(),
+                date_modified: entry.updated.to_rfc3339(),
+            })
+            .collect(),
+    };
+    serde_json::to_string(&feed).unwrap()
+}
+
+/// Links to the neighbours of a page in a paginated listing,
+/// for the template to declare with `<link rel='prev'>` and `<link rel='next'>`.
+/// A link is absent on the first or last page respectively.
+#[derive(Default, Serialize)]
+struct PageLinks {
+    prev_page: Option<String>,
+    next_page: Option<String>,
+}
+
+/// A page of the blog index: the first is `index.html` and the rest are `page/N.html`.
+#[derive(Serialize)]
+struct IndexPage {
+    /// The path from the page to the blog's directory.
+    root: &'static str,
+    /// The page's number, starting from 1.
+    page: usize,
+    pages: usize,
+}
+
+impl IndexPage {
+    fn links(&self) -> PageLinks {
+        // Links are relative to the page, so they depend on which directory it's in.
+        let href = |page: usize| match (self.page, page) {
+            (_, 1) => "../".to_owned(),
+            (1, _) => index_page_path(page),
+            (_, _) => format!("{page}.html"),
+        };
+        PageLinks {
+            prev_page: (self.page > 1).then(|| href(self.page - 1)),
+            next_page: (self.page < self.pages).then(|| href(self.page + 1)),
+        }
+    }
+}
+
+fn index_page_path(page: usize) -> String {
+    match page {
+        1 => "index.html".to_owned(),
+        _ => format!("{INDEX_PAGES_DIR}/{page}.html"),
+    }
+}
+
+/// The number of pages the blog index is split into, which is at least one
+/// so that there is an index even without any posts.
+fn page_count(posts: usize, per_page: NonZeroUsize) -> usize {
+    posts.div_ceil(per_page.get()).max(1)
+}
+
+/// The posts on each page of the blog index, in order.
+fn page_slices(posts: &[Rc<Post>], per_page: NonZeroUsize) -> impl Iterator<Item = &[Rc<Post>]> {
+    let empty: &[Rc<Post>] = &[];
+    posts
+        .chunks(per_page.get())
+        .chain(posts.is_empty().then_some(empty))
+}
+
+/// Render a page listing posts, such as the index or a collection,
+/// with `extra` variables added for the template.
+fn build_listing(
+    posts: &[Rc<Post>],
+    links: &PageLinks,
+    extra: impl Serialize,
     templater: &Templater,
     template: &anyhow::Result<Template>,
 ) -> Result<String, ErrorPage> {
     #[derive(Serialize)]
-    struct TemplateVars<'a> {
+    struct TemplateVars<'a, E> {
+        /// Every post, including its already rendered Markdown as `content.markdown`:
+        /// `title`, `body`, `summary` and `outline` are all available, so a listing can show
+        /// posts in full without rendering them again.
         posts: &'a [Rc<Post>],
+        /// Published posts grouped by the year they were published in, newest first.
+        years: Vec<Year<'a>>,
+        #[serde(flatten)]
+        links: &'a PageLinks,
         feed: &'static str,
+        rss_feed: &'static str,
+        json_feed: &'static str,
+        #[serde(flatten)]
+        extra: E,
+    }
+    #[derive(Serialize)]
+    struct Year<'a> {
+        year: i32,
+        posts: Vec<&'a Rc<Post>>,
     }
+
+    let mut years = Vec::<Year<'_>>::new();
+    for post in posts {
+        let Some(published) = post.published() else {
+            continue;
+        };
+        match years.last_mut() {
+            Some(year) if year.year == published.year() => year.posts.push(post),
+            _ => years.push(Year {
+                year: published.year(),
+                posts: vec![post],
+            }),
+        }
+    }
+
     let vars = TemplateVars {
         posts,
+        years,
+        links,
         feed: FEED_PATH,
+        rss_feed: RSS_FEED_PATH,
+        json_feed: JSON_FEED_PATH,
+        extra,
     };
     Ok(templater.render(template.as_ref()?, vars)?)
 }
 
+/// The posts published just before and after a post, for it to link to.
+#[derive(Default, Clone, PartialEq, Serialize)]
+struct Neighbours {
+    prev: Option<PostLink>,
+    next: Option<PostLink>,
+}
+
+#[derive(Clone, PartialEq, Serialize)]
+struct PostLink {
+    stem: Rc<str>,
+    title: String,
+}
+
+impl Neighbours {
+    /// Find the neighbours of the post `stem` in `posts`, which is sorted newest first.
+    /// Posts that failed to build are skipped, as are drafts unless they are being built.
+    fn new(posts: &[Rc<Post>], stem: &str) -> Self {
+        let link = |post: &Post| {
+            let content = post.content.as_ref().ok()?;
+            Some(PostLink {
+                stem: post.stem.clone(),
+                title: content.markdown.title.clone(),
+            })
+        };
+        let Some(i) = posts.iter().position(|other| *other.stem == *stem) else {
+            return Self::default();
+        };
+        Self {
+            prev: posts[i + 1..].iter().find_map(|post| link(post)),
+            next: posts[..i].iter().rev().find_map(|post| link(post)),
+        }
+    }
+}
+
+/// `css_path` is the logical path of the post CSS in the manifest.
 fn build_post(
     post: &Post,
+    neighbours: &Neighbours,
     templater: &Templater,
     template: Result<&Template, &anyhow::Error>,
+    css_path: &str,
 ) -> Result<String, ErrorPage> {
     let (post_content, template) = ErrorPage::zip(post.content.as_ref(), template)?;
 
     #[derive(Serialize)]
     struct TemplateVars<'a> {
         post: &'a PostContent,
-        post_css: &'static str,
+        /// The post's tags along with the paths of their pages.
+        tags: Vec<TagLink<'a>>,
+        #[serde(flatten)]
+        neighbours: &'a Neighbours,
+        post_css: &'a str,
         feed: &'static str,
     }
+    #[derive(Serialize)]
+    struct TagLink<'a> {
+        name: &'a str,
+        href: String,
+    }
+    let tags = post_content
+        .metadata
+        .tags
+        .iter()
+        .map(|name| (name, tag_slug(name)))
+        .filter(|(_, slug)| !slug.is_empty())
+        .map(|(name, slug)| TagLink {
+            name,
+            href: format!("{TAGS_DIR}/{}", tag_href(&slug)),
+        })
+        .collect();
+    let post_css = templater.asset_url(css_path);
     let vars = TemplateVars {
         post: post_content,
-        post_css: POST_CSS_PATH,
+        tags,
+        neighbours,
+        post_css: &post_css,
         feed: FEED_PATH,
     };
 
-    Ok(templater.render(template, vars)?)
-}
-
-fn theme_asset(path: PathBuf) -> impl Asset<Output = Rc<String>> {
-    asset::FsPath::new(path.clone())
-        .map(move |()| {
-            let res = ThemeSet::get_theme(&path)
-                .with_context(|| format!("failed to read theme file {}", path.display()));
-            Rc::new(match res {
-                Ok(theme) => markdown::theme_css(&theme),
-                Err(e) => {
-                    log::error!("{e:?}");
-                    String::new()
-                }
-            })
-        })
-        .cache()
+    Ok(templater.render_with_css(template, vars, &[&post_css])?)
 }
 
 fn serialize_unwrap<S, T, E>(result: &Result<T, E>, serializer: S) -> Result<S::Ok, S::Error>
@@ -401,27 +1543,212 @@ where
         .serialize(serializer)
 }
 
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn reading_time() {
+        assert_eq!(super::reading_time(""), 1);
+        let prose = "<p>word <em>word</em></p>".repeat(220);
+        assert_eq!(super::reading_time(&prose), 2);
+        let code = "<pre class='scode'><code>a b c</code></pre><code class='scode'>d</code>";
+        assert_eq!(
+            super::reading_time(&format!("{prose}{}", code.repeat(500))),
+            2
+        );
+    }
+
+    #[test]
+    fn front_matter() {
+        let (metadata, markdown) = split_front_matter("{ \"title\": \"a\" }\n# b").unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("a"));
+        assert_eq!(markdown, "\n# b");
+
+        let (metadata, markdown) = split_front_matter("# b").unwrap();
+        assert_eq!(metadata.title, None);
+        assert_eq!(markdown, "# b");
+
+        // A mistake in the front matter isn't taken to mean there is none.
+        let src = "{ \"published\": \"2020-01-01\", \"markdown\": { \"tabels\": true } }\n# b";
+        assert!(split_front_matter(src).is_err());
+    }
+
+    #[test]
+    fn feed_entries() {
+        let read = |entries: &str| {
+            let json = format!(r#"{{ "url": "/blog/", "title": "Blog"{entries} }}"#);
+            serde_json::from_str::<super::FeedMetadata>(&json).map(|metadata| metadata.feed_entries)
+        };
+        assert_eq!(read("").unwrap(), Some(10));
+        assert_eq!(read(r#", "feed_entries": 5"#).unwrap(), Some(5));
+        assert_eq!(read(r#", "feed_entries": 0"#).unwrap(), None);
+        assert_eq!(read(r#", "feed_entries": "all""#).unwrap(), None);
+        assert!(read(r#", "feed_entries": "some""#).is_err());
+        assert_eq!(read(r#", "feed_length": 3"#).unwrap(), Some(3));
+    }
+
+    #[test]
+    fn rss() {
+        let post = test_post("post", r#"{ "published": "2022-01-01" } # A `b` & *c*"#);
+        let metadata = r#"{ "url": "/blog/", "title": "Blog" }"#;
+        let metadata = serde_json::from_str::<super::FeedMetadata>(metadata).unwrap();
+        let rss = super::build_rss(&[post], &metadata, &site::Metadata::default(), "fr");
+        assert!(rss.contains("<language>fr</language>"));
+        assert!(rss.contains("<item><title>A b &amp; c</title>"));
+    }
+
+    #[test]
+    fn feed_length() {
+        // Unpublished posts don't take up any of the feed's entries.
+        let unpublished = test_post("unpublished", "# Unpublished");
+        let published = (1..=12).rev().map(|day| {
+            let src = format!(r#"{{ "published": "2022-01-{day:02}" }} # Post"#);
+            test_post(&format!("post-{day}"), &src)
+        });
+        let posts: Vec<_> = std::iter::once(unpublished).chain(published).collect();
+
+        let entries = |length: &str| {
+            let json =
+                format!(r#"{{ "url": "/blog/", "title": "Blog", "feed_entries": {length} }}"#);
+            let metadata = serde_json::from_str::<super::FeedMetadata>(&json).unwrap();
+            let feed =
+                super::build_feed(&posts, &metadata, &site::Metadata::default(), false, "en");
+            feed.matches("<entry>").count()
+        };
+        assert_eq!(entries("10"), 10);
+        assert_eq!(entries("3"), 3);
+        assert_eq!(entries("0"), 12);
+        assert_eq!(entries(r#""all""#), 12);
+    }
+
+    #[test]
+    fn pagination() {
+        let per_page = NonZeroUsize::new(10).unwrap();
+        assert_eq!(super::page_count(0, per_page), 1);
+        assert_eq!(super::page_count(9, per_page), 1);
+        assert_eq!(super::page_count(10, per_page), 1);
+        assert_eq!(super::page_count(11, per_page), 2);
+        assert_eq!(super::page_count(30, per_page), 3);
+        assert_eq!(super::page_slices(&[], per_page).count(), 1);
+
+        let links = |page, pages| {
+            let links = IndexPage {
+                root: "",
+                page,
+                pages,
+            }
+            .links();
+            (links.prev_page, links.next_page)
+        };
+        assert_eq!(links(1, 1), (None, None));
+        assert_eq!(links(1, 3), (None, Some("page/2.html".to_owned())));
+        assert_eq!(
+            links(2, 3),
+            (Some("../".to_owned()), Some("3.html".to_owned()))
+        );
+        assert_eq!(links(3, 3), (Some("2.html".to_owned()), None));
+    }
+
+    #[test]
+    fn neighbours() {
+        let posts = [
+            test_post("d", "# D"),
+            test_post("c", r#"{ "published": "2022-01-03" } # C"#),
+            Rc::new(Post {
+                stem: "b".into(),
+                content: Err(anyhow!("failed")),
+            }),
+            test_post("a", r#"{ "published": "2022-01-01" } # A"#),
+        ];
+        let stems = |neighbours: Neighbours| {
+            let stem = |link: Option<PostLink>| link.map(|link| link.stem.to_string());
+            (stem(neighbours.prev), stem(neighbours.next))
+        };
+
+        let c = Neighbours::new(&posts, &posts[1].stem);
+        assert_eq!(c.prev.as_ref().unwrap().title, "A");
+        assert_eq!(stems(c), (Some("a".to_owned()), Some("d".to_owned())));
+        let a = Neighbours::new(&posts, &posts[3].stem);
+        assert_eq!(stems(a), (None, Some("c".to_owned())));
+        let d = Neighbours::new(&posts, &posts[0].stem);
+        assert_eq!(stems(d), (Some("c".to_owned()), None));
+    }
+
+    #[test]
+    fn tag_slug() {
+        assert_eq!(super::tag_slug("Rust"), "rust");
+        assert_eq!(super::tag_slug("  Type systems!"), "type-systems");
+        assert_eq!(super::tag_slug("C++/async"), "c-async");
+        assert_eq!(super::tag_slug("Ünïcode"), "ünïcode");
+        assert_eq!(super::tag_slug("???"), "");
+        assert_eq!(super::tag_href("ünïcode"), "%C3%BCn%C3%AFcode.html");
+    }
+
+    fn test_post(stem: &str, src: &str) -> Rc<Post> {
+        let path = Path::new("post.md");
+        let rendered = render_post(path, src, &markdown::Options::default());
+        Rc::new(read_post(
+            stem.into(),
+            path,
+            Ok(src.to_owned()),
+            Modified::Never,
+            Some(Rc::new(rendered)),
+        ))
+    }
+
+    use super::read_post;
+    use super::render_post;
+    use super::split_front_matter;
+    use super::IndexPage;
+    use super::Neighbours;
+    use super::Post;
+    use super::PostLink;
+    use crate::site;
+    use crate::util::asset::Modified;
+    use crate::util::markdown;
+    use anyhow::anyhow;
+    use std::num::NonZeroUsize;
+    use std::path::Path;
+    use std::rc::Rc;
+}
+
 use crate::config::Config;
+use crate::site;
+use crate::sitemap;
 use crate::templater::Templater;
 use crate::util::asset;
 use crate::util::asset::Asset;
 use crate::util::log_errors;
 use crate::util::markdown;
 use crate::util::markdown::Markdown;
-use crate::util::minify;
-use crate::util::minify::minify;
+use crate::util::push_str::escape_href;
+use crate::util::push_str::escape_html;
+use crate::util::push_str::push;
+use crate::util::remove_stale_files;
 use crate::util::write_file;
 use crate::util::ErrorPage;
+use crate::util::PanicContext;
 use anyhow::Context as _;
 use chrono::naive::NaiveDate;
+use chrono::offset::FixedOffset;
 use chrono::offset::TimeZone as _;
+use chrono::offset::Utc;
 use chrono::DateTime;
+use chrono::Datelike as _;
 use handlebars::template::Template;
+use serde::de;
+use serde::de::Unexpected;
 use serde::Deserialize;
+use serde::Deserializer;
 use serde::Serialize;
 use serde::Serializer;
 use std::cmp;
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+use std::fmt;
+use std::fmt::Formatter;
+use std::fs;
+use std::mem;
+use std::num::NonZeroUsize;
 use std::path::Path;
 use std::path::PathBuf;
 use std::rc::Rc;
-use syntect::highlighting::ThemeSet;
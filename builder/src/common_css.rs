@@ -1,16 +1,27 @@
-// TODO: Make this path a variable rather than a constant
+/// The logical path of the common CSS. It is emitted at the path the manifest maps this to.
 pub(crate) const PATH: &str = "common.css";
 
+/// Emit the common CSS, outputting the path relative to `out_path` that it was emitted at.
 pub(crate) fn asset<'a>(
     in_path: &'a Path,
+    code_themes_dir: &Path,
     out_path: &'a Path,
     config: impl Asset<Output = &'a Config> + 'a,
-) -> impl Asset<Output = ()> + 'a {
-    copy_minify(config, minify::FileType::Css, in_path, out_path.join(PATH))
+) -> impl Asset<Output = Rc<str>> + 'a {
+    copy_minify_hashed(
+        config,
+        minify::FileType::Css,
+        in_path,
+        markdown::themes_css_asset(code_themes_dir),
+        out_path,
+        PATH,
+    )
 }
 
-use crate::config::copy_minify;
+use crate::config::copy_minify_hashed;
 use crate::config::Config;
 use crate::util::asset::Asset;
+use crate::util::markdown;
 use crate::util::minify;
 use std::path::Path;
+use std::rc::Rc;
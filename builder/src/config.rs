@@ -1,18 +1,138 @@
 /// Global config shared by the entire program.
+///
+/// Everything in it but [`Self::dry_run`] may affect the output, so it is hashed to tell
+/// whether outputs from previous runs can be reused (see [`Self::fingerprint`]).
 pub(crate) struct Config {
-    /// Whether to build drafts.
+    /// Whether to build drafts: unpublished posts and reviews still marked `TODO`.
     pub drafts: bool,
 
-    /// Whether we minify the result.
-    pub minify: bool,
+    /// How to minify the result, or `None` to leave it as-is.
+    pub minify: Option<minify::Options>,
 
     /// Whether to build icons.
     pub icons: bool,
 
+    /// Icons to link to when not building them.
+    pub external_icons: icons::External,
+
     /// Whether we are live reloading.
     pub live_reload: bool,
+
+    /// The language of the site's content, as a BCP 47 language tag.
+    pub lang: String,
+
+    /// Options used when rendering Markdown.
+    pub markdown: markdown::Options,
+
+    /// File extensions of Markdown source files.
+    pub markdown_extensions: Vec<String>,
+
+    /// How to emit redirects.
+    pub redirects_format: redirects::Format,
+
+    /// If set, the only blog post to build, given by its path or file name.
+    /// The blog index and feed are not built.
+    pub only_post: Option<PathBuf>,
+
+    /// The path the site is hosted under, starting and ending with `/`.
+    pub base_path: String,
+
+    /// The origin the site is hosted at, like `https://example.com`, without a trailing slash.
+    /// Together with [`Self::base_path`], this makes URLs absolute.
+    pub base_url: Option<String>,
+
+    /// How many posts each page of the blog index lists.
+    pub posts_per_page: NonZeroUsize,
+
+    /// Paths that `robots.txt` asks crawlers not to visit.
+    pub robots_disallow: Vec<String>,
+
+    /// Which output files to also write compressed copies of.
+    pub precompress: precompress::Options,
+
+    /// Whether to only log the files that would be written, rather than writing them.
+    pub dry_run: bool,
+}
+
+impl Hash for Config {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let Self {
+            drafts,
+            minify,
+            icons,
+            external_icons,
+            live_reload,
+            lang,
+            markdown,
+            markdown_extensions,
+            redirects_format,
+            only_post,
+            base_path,
+            base_url,
+            posts_per_page,
+            robots_disallow,
+            precompress,
+            // Dry runs build the same things; they just don't write them.
+            dry_run: _,
+        } = self;
+        drafts.hash(state);
+        minify.hash(state);
+        icons.hash(state);
+        external_icons.hash(state);
+        live_reload.hash(state);
+        lang.hash(state);
+        markdown.hash(state);
+        markdown_extensions.hash(state);
+        redirects_format.hash(state);
+        only_post.hash(state);
+        base_path.hash(state);
+        base_url.hash(state);
+        posts_per_page.hash(state);
+        robots_disallow.hash(state);
+        precompress.hash(state);
+    }
+}
+
+impl Config {
+    /// A hash of the config, which is the same between runs with the same config.
+    pub(crate) fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// How to minify outputs, if at all.
+    /// Dry runs don't minify anything, since the minified output would never be written.
+    pub(crate) fn minify_options(&self) -> Option<minify::Options> {
+        self.minify.filter(|_| !self.dry_run)
+    }
+
+    /// Whether the given path has the extension of a Markdown file.
+    pub(crate) fn is_markdown(&self, path: &Path) -> bool {
+        path.extension().is_some_and(|extension| {
+            self.markdown_extensions
+                .iter()
+                .any(|markdown| extension == &**markdown)
+        })
+    }
+
+    /// The direction of text written in [`Self::lang`], either `ltr` or `rtl`.
+    pub(crate) fn dir(&self) -> &'static str {
+        let primary = self.lang.split(['-', '_']).next().unwrap();
+        if RTL_LANGUAGES
+            .iter()
+            .any(|rtl| primary.eq_ignore_ascii_case(rtl))
+        {
+            "rtl"
+        } else {
+            "ltr"
+        }
+    }
 }
 
+/// Primary language subtags of languages that are written right-to-left.
+const RTL_LANGUAGES: [&str; 10] = ["ar", "dv", "fa", "he", "ku", "ps", "sd", "ug", "ur", "yi"];
+
 pub(crate) fn copy_minify<'a>(
     config: impl Asset<Output = &'a Config> + 'a,
     file_type: minify::FileType,
@@ -23,10 +143,10 @@ pub(crate) fn copy_minify<'a>(
     asset::all((asset::TextFile::new(in_), config))
         .map(move |(res, config)| -> anyhow::Result<_> {
             let mut text = res?;
-            if config.minify {
-                minify(file_type, &mut text);
+            if let Some(options) = config.minify_options() {
+                minify(options, file_type, &mut text);
             }
-            write_file(&out_1, text)?;
+            write_file(config, &out_1, text)?;
             log::info!("successfully emitted {}", out_1.as_ref().display());
             Ok(())
         })
@@ -34,10 +154,150 @@ pub(crate) fn copy_minify<'a>(
         .modifies_path(out)
 }
 
+/// Like [`copy_minify`], but with `extra` appended to the file, and when minifying,
+/// the file is emitted under a name containing a hash of its contents (see [`hashed_path`]).
+/// Browsers cache such files aggressively, so changes to them must be served under a new name.
+/// Outputs that name, relative to `out_dir`, and removes the file's previously emitted names.
+pub(crate) fn copy_minify_hashed<'a, E: Deref<Target: AsRef<str>>>(
+    config: impl Asset<Output = &'a Config> + 'a,
+    file_type: minify::FileType,
+    in_: impl AsRef<Path> + 'a,
+    extra: impl Asset<Output = E> + 'a,
+    out_dir: &'a Path,
+    path: &'static str,
+) -> impl Asset<Output = Rc<str>> + 'a {
+    asset::all((asset::TextFile::new(in_), extra, config))
+        .with_modified()
+        .map(
+            move |(modified, (res, extra, config))| -> Rc<dyn Asset<Output = _>> {
+                let mut text = match res {
+                    Ok(text) => text,
+                    Err(e) => {
+                        log::error!("{e:?}");
+                        return Rc::new(asset::Constant::new(Rc::from(path)));
+                    }
+                };
+                text.push_str((*extra).as_ref());
+
+                let emitted: Rc<str> = if let Some(options) = config.minify_options() {
+                    minify(options, file_type, &mut text);
+                    Rc::from(hashed_path(path, &text))
+                } else {
+                    Rc::from(path)
+                };
+
+                let file_path = out_dir.join(&*emitted);
+                Rc::new(
+                    asset::Dynamic::modified_at((), modified)
+                        .map({
+                            let file_path = file_path.clone();
+                            let emitted = emitted.clone();
+                            move |()| {
+                                write_file(config, &file_path, &text)?;
+                                remove_stale_hashed(config, out_dir, path, &emitted)?;
+                                log::info!("successfully emitted {}", file_path.display());
+                                Ok(())
+                            }
+                        })
+                        .map(log_errors)
+                        .modifies_path(file_path)
+                        .map(move |()| emitted.clone()),
+                )
+            },
+        )
+        .cache()
+        .flatten()
+}
+
+/// Remove the names other than `current` that `path` may have been emitted at in `out_dir`.
+fn remove_stale_hashed(
+    config: &Config,
+    out_dir: &Path,
+    path: &str,
+    current: &str,
+) -> anyhow::Result<()> {
+    remove_stale_files(config, out_dir, |name| {
+        name != current && is_emitted_name(path, name)
+    })
+}
+
+/// Whether `name` is either `path` itself or a name [`hashed_path`] may give it.
+fn is_emitted_name(path: &str, name: &str) -> bool {
+    if name == path {
+        return true;
+    }
+    let hash = match path.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && !extension.contains('/') => name
+            .strip_prefix(stem)
+            .and_then(|rest| rest.strip_prefix('.'))
+            .and_then(|rest| rest.strip_suffix(extension))
+            .and_then(|rest| rest.strip_suffix('.')),
+        _ => name
+            .strip_prefix(path)
+            .and_then(|rest| rest.strip_prefix('.')),
+    };
+    hash.is_some_and(|hash| hash.len() == 8 && hash.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+/// Insert a short hash of `contents` into the file name of `path` before its extension,
+/// so that `common.css` becomes something like `common.1a2b3c4d.css`.
+///
+/// Emitting a file under such a name stops browsers from using stale copies from their caches.
+/// The hash isn't stable between versions of Rust, which at worst renames files needlessly.
+pub(crate) fn hashed_path(path: &str, contents: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    let hash = format!("{:016x}", hasher.finish());
+    let hash = &hash[..8];
+    match path.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && !extension.contains('/') => {
+            format!("{stem}.{hash}.{extension}")
+        }
+        _ => format!("{path}.{hash}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn hashed_path() {
+        let a = super::hashed_path("common.css", "a");
+        assert_eq!(a, format!("common.{}.css", &a[7..15]));
+        assert_eq!(a, super::hashed_path("common.css", "a"));
+        assert_ne!(a, super::hashed_path("common.css", "b"));
+
+        assert!(super::hashed_path("dir.d/file", "a").starts_with("dir.d/file."));
+        assert!(super::hashed_path(".hidden", "a").starts_with(".hidden."));
+    }
+
+    #[test]
+    fn is_emitted_name() {
+        for path in ["common.css", ".hidden"] {
+            assert!(super::is_emitted_name(path, path));
+            assert!(super::is_emitted_name(path, &super::hashed_path(path, "a")));
+        }
+        assert!(!super::is_emitted_name("common.css", "common.print.css"));
+        assert!(!super::is_emitted_name("common.css", "common.css.map"));
+        assert!(!super::is_emitted_name("common.css", "post.1a2b3c4d.css"));
+    }
+}
+
 use crate::asset;
+use crate::icons;
+use crate::redirects;
 use crate::util::asset::Asset;
 use crate::util::log_errors;
+use crate::util::markdown;
 use crate::util::minify;
 use crate::util::minify::minify;
+use crate::util::precompress;
+use crate::util::remove_stale_files;
 use crate::util::write_file;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::num::NonZeroUsize;
+use std::ops::Deref;
 use std::path::Path;
+use std::path::PathBuf;
+use std::rc::Rc;
@@ -1,5 +1,4 @@
-// Used in templates
-#[derive(Clone, Copy, Serialize)]
+#[derive(Clone, Copy)]
 pub(crate) struct Paths {
     pub(crate) favicon: &'static str,
     pub(crate) apple_touch_icon: &'static str,
@@ -10,6 +9,51 @@ pub(crate) const PATHS: Paths = Paths {
     apple_touch_icon: "apple-touch-icon.png",
 };
 
+/// URLs of icons hosted elsewhere, to link to when they aren't generated.
+#[derive(Hash, clap::Args)]
+pub(crate) struct External {
+    /// URL of a favicon to link to instead of a generated one.
+    #[clap(long = "favicon-url", requires = "apple_touch_icon")]
+    pub favicon: Option<String>,
+    /// URL of an Apple touch icon to link to instead of a generated one.
+    #[clap(long = "apple-touch-icon-url", requires = "favicon")]
+    pub apple_touch_icon: Option<String>,
+}
+
+// Used in templates
+/// The links to the site's icons that pages include.
+#[derive(Serialize)]
+pub(crate) struct Links {
+    pub(crate) favicon: String,
+    pub(crate) apple_touch_icon: String,
+    /// Whether the icons are generated by the build, as opposed to being hosted elsewhere.
+    pub(crate) local: bool,
+}
+
+impl Links {
+    /// The links for the given configuration, if there are any icons at all.
+    /// Generated icons take precedence over external ones.
+    pub(crate) fn new(config: &Config) -> Option<Self> {
+        if config.icons {
+            Some(Self {
+                favicon: format!("{}{}", config.base_path, PATHS.favicon),
+                apple_touch_icon: format!("{}{}", config.base_path, PATHS.apple_touch_icon),
+                local: true,
+            })
+        } else {
+            let External {
+                favicon,
+                apple_touch_icon,
+            } = &config.external_icons;
+            Some(Self {
+                favicon: favicon.clone()?,
+                apple_touch_icon: apple_touch_icon.clone()?,
+                local: false,
+            })
+        }
+    }
+}
+
 pub(crate) fn asset<'a>(
     input_path: &'a Path,
     output_path: &'a Path,
@@ -18,7 +62,7 @@ pub(crate) fn asset<'a>(
     config
         .map(|config| -> Box<dyn Asset<Output = ()> + 'a> {
             if config.icons {
-                Box::new(real_asset(input_path, output_path))
+                Box::new(real_asset(input_path, output_path, config))
             } else {
                 Box::new(asset::Constant::new(()))
             }
@@ -26,7 +70,11 @@ pub(crate) fn asset<'a>(
         .flatten()
 }
 
-fn real_asset<'a>(input_path: &'a Path, output_path: &'a Path) -> impl Asset<Output = ()> + 'a {
+fn real_asset<'a>(
+    input_path: &'a Path,
+    output_path: &'a Path,
+    config: &'a Config,
+) -> impl Asset<Output = ()> + 'a {
     asset::FsPath::new(input_path)
         .map(move |()| -> anyhow::Result<()> {
             let image = image::open(input_path)
@@ -34,18 +82,19 @@ fn real_asset<'a>(input_path: &'a Path, output_path: &'a Path) -> impl Asset<Out
 
             let filter = image::imageops::FilterType::CatmullRom;
 
+            let mut apple_touch_icon = Cursor::new(Vec::new());
             image
                 .resize(APPLE_TOUCH_ICON_SIZE, APPLE_TOUCH_ICON_SIZE, filter)
-                .save(output_path.join(PATHS.apple_touch_icon))
-                .with_context(|| format!("couldn't save to {}", PATHS.apple_touch_icon))?;
-
-            let favicon_path = output_path.join(PATHS.favicon);
-            let mut file = BufWriter::new(
-                File::create(&favicon_path)
-                    .with_context(|| format!("failed to create {}", favicon_path.display()))?,
-            );
+                .write_to(&mut apple_touch_icon, ImageOutputFormat::Png)
+                .with_context(|| format!("couldn't encode {}", PATHS.apple_touch_icon))?;
+            write_file(
+                config,
+                output_path.join(PATHS.apple_touch_icon),
+                apple_touch_icon.into_inner(),
+            )?;
 
-            IcoEncoder::new(&mut file)
+            let mut favicon = Vec::new();
+            IcoEncoder::new(&mut favicon)
                 .encode_images(
                     &ICO_SIZES
                         .into_iter()
@@ -61,9 +110,8 @@ fn real_asset<'a>(input_path: &'a Path, output_path: &'a Path) -> impl Asset<Out
                         })
                         .collect::<Result<Vec<_>, _>>()?,
                 )
-                .context("failed to write to favicon.ico")?;
-
-            file.flush().context("failed to flush favicon.ico")?;
+                .context("failed to encode favicon.ico")?;
+            write_file(config, output_path.join(PATHS.favicon), favicon)?;
 
             log::info!("successfully emitted favicon files");
 
@@ -83,12 +131,12 @@ const APPLE_TOUCH_ICON_SIZE: u32 = 180;
 use crate::util::asset;
 use crate::util::asset::Asset;
 use crate::util::log_errors;
+use crate::util::write_file;
 use crate::Config;
 use anyhow::Context as _;
 use image::codecs::ico::IcoEncoder;
 use image::codecs::ico::IcoFrame;
+use image::ImageOutputFormat;
 use serde::Serialize;
-use std::fs::File;
-use std::io::BufWriter;
-use std::io::Write as _;
+use std::io::Cursor;
 use std::path::Path;
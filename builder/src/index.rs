@@ -3,19 +3,37 @@ pub(crate) fn asset<'a>(
     src_path: &'a Path,
     out_path: &'a Path,
     templater: impl Asset<Output = Templater> + Clone + 'a,
+    config: impl Asset<Output = &'a Config> + Copy + 'a,
 ) -> impl Asset<Output = ()> + 'a {
     let template = asset::TextFile::new(template_path)
-        .map(|src| Template::compile(&src?).context("failed to compile index template"))
-        .map(Rc::new)
+        .and_then(|src| Template::compile(&src).context("failed to compile index template"))
+        .map(|res| res.map(Rc::new).map_err(Rc::new))
         .cache();
 
-    let markdown = asset::TextFile::new(src_path)
-        .map(|src| Rc::new(src.map(|src| markdown::parse(&src))))
+    let markdown = asset::all((asset::TextFile::new(src_path), config))
+        .map(|(src, config)| {
+            let markdown = src.map(|src| {
+                let mut json = serde_json::Deserializer::from_str(&src).into_iter();
+                let metadata: Metadata = json.next().and_then(Result::ok).unwrap_or_default();
+                let markdown = &src[json.byte_offset()..];
+
+                log::debug!("parsing {}", src_path.display());
+                let _context = PanicContext(format!("parsing {}", src_path.display()));
+                let mut markdown = markdown::parse(markdown, &config.markdown);
+                let front_matter = &src[..json.byte_offset()];
+                markdown.log_diagnostics(src_path, front_matter.matches('\n').count());
+                if let Some(summary) = metadata.summary {
+                    markdown.summary = summary;
+                }
+                markdown
+            });
+            markdown.map(Rc::new).map_err(Rc::new)
+        })
         .cache();
 
-    asset::all((markdown, templater, template))
-        .map(|(markdown, templater, template)| {
-            let (markdown, template) = ErrorPage::zip((*markdown).as_ref(), (*template).as_ref())?;
+    let html = asset::all((markdown.zip(template), templater)).map(
+        |(markdown_and_template, templater)| {
+            let (markdown, template) = markdown_and_template?;
 
             #[derive(Serialize)]
             struct TemplateVars<'a> {
@@ -26,10 +44,13 @@ pub(crate) fn asset<'a>(
                 body: &markdown.body,
                 summary: &markdown.summary,
             };
-            Ok(templater.render(template, vars)?)
-        })
-        .map(move |html| {
-            write_file(out_path, html.unwrap_or_else(ErrorPage::into_html))?;
+            Ok(templater.render(&template, vars)?)
+        },
+    );
+
+    asset::all((html, config))
+        .map(move |(html, config)| {
+            write_file(config, out_path, html.unwrap_or_else(ErrorPage::into_html))?;
             log::info!("successfully emitted index.html");
             Ok(())
         })
@@ -37,6 +58,15 @@ pub(crate) fn asset<'a>(
         .modifies_path(out_path)
 }
 
+/// Optional JSON front matter of the index page.
+#[derive(Default, Deserialize)]
+struct Metadata {
+    /// Overrides the summary extracted from the first paragraph.
+    #[serde(alias = "description")]
+    summary: Option<String>,
+}
+
+use crate::config::Config;
 use crate::templater::Templater;
 use crate::util::asset;
 use crate::util::asset::Asset;
@@ -44,8 +74,10 @@ use crate::util::log_errors;
 use crate::util::markdown;
 use crate::util::write_file;
 use crate::util::ErrorPage;
+use crate::util::PanicContext;
 use anyhow::Context as _;
 use handlebars::Template;
+use serde::Deserialize;
 use serde::Serialize;
 use std::path::Path;
 use std::rc::Rc;
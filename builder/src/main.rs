@@ -21,23 +21,30 @@ mod icons;
 mod index;
 mod not_found;
 mod raw;
+mod redirects;
 mod reviews;
+mod robots;
 #[cfg(feature = "server")]
 mod server;
+mod site;
+mod sitemap;
 mod templater;
 
 mod config;
+use config::copy_minify_hashed;
 use config::Config;
 
 mod util;
 use self::util::asset;
 use self::util::asset::Asset;
+use self::util::markdown;
 use self::util::minify;
+use self::util::precompress;
 
 /// Rust program that builds this website.
 #[derive(clap::Parser)]
 struct Args {
-    /// Whether to build drafts.
+    /// Whether to build drafts: unpublished posts and reviews still marked `TODO`.
     #[clap(long)]
     drafts: bool,
 
@@ -45,26 +52,229 @@ struct Args {
     #[clap(long)]
     no_icons: bool,
 
+    #[clap(flatten)]
+    external_icons: icons::External,
+
     /// Whether to minify the output.
     #[clap(long)]
     minify: bool,
 
+    /// Leave files smaller than this many bytes unminified.
+    #[clap(long, default_value_t = 256)]
+    minify_threshold: usize,
+
+    /// Seconds after which a hanging minifier is killed, leaving its input unminified.
+    #[clap(long, default_value_t = 60)]
+    minify_timeout: u64,
+
     /// Whether to watch the directory for changes.
     #[clap(long)]
     watch: bool,
 
-    /// Output directory.
-    #[clap(short, default_value = "dist")]
-    output: String,
+    /// Log the files that would be written instead of writing them.
+    #[clap(long, conflicts_with_all = ["watch", "serve_port"])]
+    dry_run: bool,
+
+    /// Print the paths of the files the build may write, then exit without writing anything.
+    #[clap(long, conflicts_with_all = ["watch", "serve_port"])]
+    list_outputs: bool,
+
+    /// Fail the build if any errors were logged,
+    /// instead of writing fallback output in their place and succeeding.
+    #[clap(long, conflicts_with_all = ["watch", "serve_port"])]
+    strict: bool,
+
+    #[clap(flatten)]
+    paths: Paths,
+
+    /// Build only the blog post with the given path or file name, skipping the blog index and
+    /// feed. Other pages are still built.
+    #[clap(long)]
+    only: Option<PathBuf>,
+
+    /// The language of the site's content, as a BCP 47 language tag.
+    #[clap(long, default_value = "en")]
+    lang: String,
+
+    /// The path the site is hosted under, e.g. `/project/` for `https://example.com/project/`.
+    #[clap(long, default_value = "/")]
+    base_path: String,
+
+    /// The origin the site is hosted at, e.g. `https://example.com`,
+    /// used by templates to make absolute URLs.
+    #[clap(long)]
+    base_url: Option<String>,
+
+    /// How many posts each page of the blog index lists.
+    #[clap(long, default_value = "10")]
+    posts_per_page: NonZeroUsize,
+
+    /// A path that `robots.txt` asks crawlers not to visit. May be given multiple times.
+    #[clap(long)]
+    robots_disallow: Vec<String>,
+
+    /// How to link Markdown headings to themselves.
+    #[clap(long, value_enum, default_value_t)]
+    anchor_style: markdown::AnchorStyle,
+
+    /// The text inside Markdown heading anchor links, unless they wrap the heading text.
+    #[clap(long, default_value = "")]
+    anchor_text: String,
+
+    /// Whether to support `<!-- type=a -->` hints before ordered lists in Markdown.
+    #[clap(long)]
+    list_type_hints: bool,
+
+    /// Give every paragraph a stable ID so that it can be linked to.
+    #[clap(long)]
+    paragraph_ids: bool,
+
+    /// Load the first image of each page eagerly with high priority, and lazy-load the rest.
+    #[clap(long)]
+    image_loading_hints: bool,
+
+    /// A JSON manifest of resized or re-encoded variants of images,
+    /// mapping each image's URL to a list of `{"src": ..., "width": ...}` objects.
+    /// Images with variants are rendered as responsive `<picture>`s.
+    #[clap(long)]
+    image_variants: Option<PathBuf>,
+
+    /// HTML to append inside links to other sites, such as an icon.
+    #[clap(long, default_value = "")]
+    external_link_icon: String,
+
+    /// How to emit redirects.
+    #[clap(long, value_enum, default_value_t)]
+    redirects_format: redirects::Format,
+
+    #[clap(flatten)]
+    markdown_features: markdown::Features,
+
+    #[clap(flatten)]
+    precompress: precompress::Options,
+
+    /// Don't reuse rendered Markdown from previous runs, which is kept in the cache directory,
+    /// or store it there.
+    #[clap(long)]
+    no_disk_cache: bool,
+
+    /// Consider source files changed only when their contents are,
+    /// not whenever their modification times are, which tools like `git checkout` update.
+    /// The contents seen are kept in `.build-cache` in the output directory,
+    /// so restoring the output directory on a fresh checkout avoids rebuilding it.
+    #[clap(long)]
+    content_hashes: bool,
+
+    /// How many threads to render Markdown on. With 1, everything happens on the main thread.
+    #[clap(long, default_value = "1")]
+    jobs: NonZeroUsize,
+
+    /// File extensions of Markdown source files.
+    #[clap(long = "markdown-extension", default_values = ["md", "markdown", "mdown"])]
+    markdown_extensions: Vec<String>,
+
+    /// A shell command to run after each build of the site, such as to notify another tool.
+    /// May be given multiple times, in which case the commands are run in order.
+    #[clap(long = "after-build")]
+    after_build: Vec<String>,
 
     /// Serve a development server on the given port.
     /// Implies `--watch`.
     #[clap(long, conflicts_with = "watch")]
     serve_port: Option<u16>,
+
+    /// The file the development server serves for requests to a directory.
+    #[clap(long, default_value = "index.html")]
+    serve_index: String,
+
+    /// The file the development server serves for paths that match nothing,
+    /// relative to the output directory.
+    /// Single-page apps may want this to be their `index.html`.
+    #[clap(long, default_value = "404.html")]
+    serve_fallback: PathBuf,
+
+    /// The status code the development server responds with when serving the fallback file.
+    #[clap(long, default_value_t = 404, value_parser = clap::value_parser!(u16).range(100..1000))]
+    serve_fallback_status: u16,
+
+    /// How many consecutive ports to try, starting at `--serve-port`, if it is in use.
+    #[clap(long, default_value_t = 10, value_parser = clap::value_parser!(u16).range(1..))]
+    serve_port_attempts: u16,
+}
+
+/// Locations of the site's inputs and outputs, relative to the project root.
+#[derive(clap::Args)]
+struct Paths {
+    /// Output directory.
+    #[clap(short, default_value = "dist")]
+    output: String,
+
+    /// Directory containing the blog's Markdown posts.
+    #[clap(long, default_value = "src/blog")]
+    blog_src: PathBuf,
+
+    /// Directory containing the blog's templates, stylesheet and feed metadata.
+    #[clap(long, default_value = "template/blog")]
+    blog_template: PathBuf,
+
+    /// Directory to emit the blog to, relative to the output directory.
+    #[clap(long, default_value = "blog")]
+    blog_out: String,
+
+    /// Markdown source of the home page.
+    #[clap(long, default_value = "src/index.md")]
+    index_src: PathBuf,
+
+    /// Template of the 404 page.
+    #[clap(long, default_value = "template/404.hbs")]
+    not_found_template: PathBuf,
+
+    /// Directory of files copied to the output as-is, such as `CNAME` and `.nojekyll`.
+    /// It need not exist.
+    #[clap(long, default_value = "raw")]
+    raw: PathBuf,
+
+    /// JSON file of metadata about the site, such as its title and author.
+    #[clap(long, default_value = "template/site.json")]
+    site_metadata: PathBuf,
+
+    /// TOML file mapping old paths of the site to the paths they redirect to.
+    /// It need not exist.
+    #[clap(long, default_value = "src/redirects.toml")]
+    redirects: PathBuf,
+
+    /// Image to generate the site's icons from.
+    #[clap(long, default_value = "src/icon.png")]
+    icon: PathBuf,
+
+    /// Directory to keep what is reused between runs in.
+    /// It is separate from the output directory so that it isn't deployed.
+    #[clap(long, default_value = ".cache")]
+    cache: PathBuf,
+}
+
+impl Paths {
+    fn validate(&self, icons: bool) -> anyhow::Result<()> {
+        for dir in [&self.blog_src, &self.blog_template] {
+            ensure!(dir.is_dir(), "directory `{}` does not exist", dir.display());
+        }
+        let icon = icons.then_some(&self.icon);
+        for file in [
+            &self.index_src,
+            &self.not_found_template,
+            &self.site_metadata,
+        ]
+        .into_iter()
+        .chain(icon)
+        {
+            ensure!(file.is_file(), "file `{}` does not exist", file.display());
+        }
+        Ok(())
+    }
 }
 
 fn main() -> anyhow::Result<()> {
-    pretty_env_logger::init();
+    util::init_logger();
 
     let args: Args = clap::Parser::parse();
 
@@ -75,33 +285,164 @@ fn main() -> anyhow::Result<()> {
         "server is not enabled; rebuild with `--features server` and try again"
     );
 
+    args.paths.validate(!args.no_icons)?;
+    ensure!(
+        !args.serve_index.is_empty()
+            && !args.serve_index.starts_with('.')
+            && !args.serve_index.contains(['/', '\\']),
+        "index file `{}` is not a plain file name",
+        args.serve_index,
+    );
+    ensure!(
+        args.serve_fallback
+            .components()
+            .all(|component| matches!(component, Component::Normal(_))),
+        "fallback file `{}` is not a path within the output directory",
+        args.serve_fallback.display(),
+    );
+
+    if args.content_hashes {
+        asset::use_content_hashes(Path::new(&args.paths.output).join(".build-cache"));
+    }
+    if args.jobs.get() > 1 {
+        asset::init_thread_pool(args.jobs.get());
+    }
+
+    let base_path = match args.base_path.trim_matches('/') {
+        "" => "/".to_owned(),
+        path => format!("/{path}/"),
+    };
+
+    let base_url = args
+        .base_url
+        .map(|url| url.trim_end_matches('/').to_owned());
+
+    let image_variants = match &args.image_variants {
+        Some(path) => markdown::read_image_variants(path)?,
+        None => markdown::ImageVariants::new(),
+    };
+
     let config = Config {
         drafts: args.drafts,
-        minify: args.minify,
+        minify: args.minify.then_some(minify::Options {
+            threshold: args.minify_threshold,
+            timeout: Duration::from_secs(args.minify_timeout),
+        }),
         icons: !args.no_icons,
+        external_icons: args.external_icons,
         live_reload: args.serve_port.is_some(),
+        lang: args.lang,
+        markdown: markdown::Options {
+            anchor_style: args.anchor_style,
+            anchor_text: args.anchor_text,
+            list_type_hints: args.list_type_hints,
+            paragraph_ids: args.paragraph_ids,
+            image_loading_hints: args.image_loading_hints,
+            image_variants,
+            external_link_icon: args.external_link_icon,
+            features: args.markdown_features,
+            footnote_prefix: String::new(),
+            base_path: base_path.clone(),
+            origin: base_url.clone().unwrap_or_default(),
+        },
+        markdown_extensions: args.markdown_extensions,
+        redirects_format: args.redirects_format,
+        only_post: args.only,
+        base_path: base_path.clone(),
+        base_url,
+        posts_per_page: args.posts_per_page,
+        robots_disallow: args.robots_disallow,
+        precompress: args.precompress,
+        dry_run: args.dry_run || args.list_outputs,
     };
 
+    let fingerprint = config.fingerprint();
+    if !args.no_disk_cache {
+        asset::init_disk_cache(
+            args.paths.cache.join("rendered"),
+            fingerprint,
+            config.dry_run,
+        );
+    }
+
     let bump = Bump::new();
-    let asset = asset(&bump, &args.output, asset::Dynamic::new(&config));
-    asset.generate();
+    let asset = asset(
+        &bump,
+        &args.paths,
+        asset::Dynamic::from_fingerprint(&config, fingerprint),
+    );
+
+    if args.list_outputs {
+        // Some assets' outputs are only known once they have been generated,
+        // so do a dry run first to fill the caches they are read from.
+        asset.generate();
+        let mut paths = Vec::new();
+        asset.output_paths(&mut paths);
+        // Assets shared between others, such as the common CSS, are reached more than once.
+        let mut seen = HashSet::new();
+        for path in paths.iter().filter(|path| seen.insert(*path)) {
+            println!("{}", path.display());
+        }
+        return Ok(());
+    }
+
+    // Steps to run after every build of the whole site, in both one-shot and watch mode.
+    let mut after_generate = Vec::<Box<dyn Fn()>>::new();
+    if args.content_hashes && !config.dry_run {
+        after_generate.push(Box::new(asset::save_content_hashes));
+    }
+    for command in &args.after_build {
+        after_generate.push(Box::new(move || util::log_errors(run_command(command))));
+    }
+    let generate = || {
+        asset.generate();
+        for hook in &after_generate {
+            hook();
+        }
+    };
+
+    generate();
+
+    if args.strict {
+        let errors = util::error_count();
+        ensure!(errors == 0, "build failed with {errors} errors");
+    }
 
     if args.watch || args.serve_port.is_some() {
         let (sender, receiver) = channel::bounded::<anyhow::Result<()>>(1);
 
         #[cfg(feature = "server")]
         let server = if let Some(port) = args.serve_port {
-            let server = server::Server::new(Path::new(&args.output));
+            let server = server::Server::new(
+                Path::new(&args.paths.output),
+                &base_path,
+                &args.serve_index,
+                &args.paths.redirects,
+                &args.serve_fallback,
+                hyper::http::StatusCode::from_u16(args.serve_fallback_status)
+                    .context("invalid fallback status")?,
+            );
             std::thread::spawn({
                 let sender = sender.clone();
                 let server = server.clone();
-                move || sender.send(server.listen(port).map(|infallible| match infallible {}))
+                move || {
+                    sender.send(
+                        server
+                            .listen(port, args.serve_port_attempts)
+                            .map(|infallible| match infallible {}),
+                    )
+                }
             });
             Some(server)
         } else {
             None
         };
 
+        let cwd = env::current_dir().context("couldn't get cwd")?;
+        let output_dir = cwd.join(&args.paths.output);
+        let node_modules = cwd.join(minify::DIR).join("node_modules");
+        let cache_dir = cwd.join(&args.paths.cache);
+
         let mut watcher = notify::recommended_watcher(move |event_res| {
             // TODO: more fine grained tracking of `notify::Event`s?
             let event: notify::Event = match event_res {
@@ -115,7 +456,25 @@ fn main() -> anyhow::Result<()> {
                 return;
             }
 
-            drop(sender.try_send(Ok(())));
+            // Events without paths, such as requests to rescan, could be from anywhere.
+            let within = |dir: &Path| {
+                !event.paths.is_empty()
+                    && event
+                        .paths
+                        .iter()
+                        .all(|path| cwd.join(path).starts_with(dir))
+            };
+            if within(&node_modules) || within(&cache_dir) {
+                return;
+            }
+
+            // The build's own writes to the output directory shouldn't trigger a rebuild,
+            // but the server still needs to know about them to reload pages.
+            if !within(&output_dir) {
+                // Files can change without their modification times or sizes changing.
+                asset::rehash(&event.paths);
+                drop(sender.try_send(Ok(())));
+            }
 
             #[cfg(feature = "server")]
             if let Some(server) = &server {
@@ -138,7 +497,7 @@ fn main() -> anyhow::Result<()> {
                 msg?;
             }
             log::debug!("rebuilding");
-            asset.generate();
+            generate();
         }
     }
 
@@ -147,54 +506,152 @@ fn main() -> anyhow::Result<()> {
 
 fn asset<'asset>(
     bump: &'asset Bump,
-    output: &'asset str,
+    paths: &'asset Paths,
     config: impl Asset<Output = &'asset Config> + Copy + 'asset,
 ) -> impl Asset<Output = ()> + 'asset {
-    let templater = Rc::new(templater::asset("template/include".as_ref(), config));
-
-    asset::all((
-        // This must come first to initialize minification
-        config
-            .map(|config| -> Box<dyn Asset<Output = ()>> {
-                if config.minify {
-                    Box::new(minify::asset())
-                } else {
-                    Box::new(asset::Constant::new(()))
-                }
+    let output = &*paths.output;
+    let common_css = Rc::new(common_css::asset(
+        "template/common.css".as_ref(),
+        &paths.blog_template.join("code_themes"),
+        Path::new(output),
+        config,
+    ));
+    let blog_out = &*util::bump::alloc_str_concat(bump, &[output, "/", &paths.blog_out]);
+    // Code highlighting themes are part of the common CSS.
+    let post_css = copy_minify_hashed(
+        config,
+        minify::FileType::Css,
+        paths.blog_template.join("post.css"),
+        asset::Constant::new(""),
+        Path::new(blog_out),
+        blog::POST_CSS_PATH,
+    );
+    let post_css_path =
+        &*util::bump::alloc_str_concat(bump, &[&paths.blog_out, "/", blog::POST_CSS_PATH]);
+    let manifest = Rc::new(
+        asset::all((common_css.clone(), post_css))
+            .map(move |(common_css, post_css)| {
+                Arc::new(templater::Manifest::from([
+                    (common_css::PATH.to_owned(), common_css.to_string()),
+                    (
+                        post_css_path.to_owned(),
+                        format!("{}/{post_css}", paths.blog_out),
+                    ),
+                ]))
             })
-            .flatten(),
+            .cache(),
+    );
+    let site = Rc::new(site::asset(&paths.site_metadata));
+    let templater = Rc::new(templater::asset(
+        "template/include".as_ref(),
+        manifest,
+        site.clone(),
+        config,
+    ));
+
+    let minify_init = config
+        .map(|config| -> Box<dyn Asset<Output = ()>> {
+            if config.minify_options().is_some() {
+                Box::new(minify::asset())
+            } else {
+                Box::new(asset::Constant::new(()))
+            }
+        })
+        .flatten()
+        .catch_unwind();
+
+    let pages = asset::all((
         blog::asset(
-            "template/blog".as_ref(),
-            "src/blog".as_ref(),
-            Path::new(util::bump::alloc_str_concat(bump, &[output, "/blog"])),
+            &paths.blog_template,
+            &paths.blog_src,
+            Path::new(blog_out),
+            post_css_path,
             templater.clone(),
+            site.clone(),
             config,
-        ),
-        //reviews::asset(
-        //    "src/reviews.toml".as_ref(),
-        //    "template/reviews.hbs".as_ref(),
-        //    "template/reviews.css".as_ref(),
-        //    "template/reviews.js".as_ref(),
-        //    Path::new(output),
-        //    templater.clone(),
-        //    config,
-        //),
+        )
+        .catch_unwind(),
+        reviews::asset(
+            "src/reviews.toml".as_ref(),
+            "template/reviews.hbs".as_ref(),
+            "template/reviews.css".as_ref(),
+            "template/reviews.js".as_ref(),
+            &paths.raw,
+            Path::new(output),
+            templater.clone(),
+            config,
+        )
+        .catch_unwind(),
         index::asset(
             "template/index.hbs".as_ref(),
-            "src/index.md".as_ref(),
+            &paths.index_src,
             Path::new(util::bump::alloc_str_concat(bump, &[output, "/index.html"])),
             templater.clone(),
-        ),
+            config,
+        )
+        .catch_unwind(),
         not_found::asset(
-            "template/404.hbs".as_ref(),
+            &paths.not_found_template,
             Path::new(util::bump::alloc_str_concat(bump, &[output, "/404.html"])),
             templater,
-        ),
-        common_css::asset("template/common.css".as_ref(), Path::new(output), config),
-        icons::asset("src/icon.png".as_ref(), Path::new(output), config),
-        raw::asset("raw".as_ref(), Path::new(output)),
+            config,
+        )
+        .catch_unwind(),
+        common_css.map(|_| {}).catch_unwind(),
+        icons::asset(&paths.icon, Path::new(output), config).catch_unwind(),
+        raw::asset(&paths.raw, Path::new(output), config).catch_unwind(),
+        robots::asset(
+            Path::new(util::bump::alloc_str_concat(bump, &[output, "/robots.txt"])),
+            config,
+        )
+        .catch_unwind(),
+        sitemap::asset(
+            Path::new(util::bump::alloc_str_concat(
+                bump,
+                &[output, "/sitemap.xml"],
+            )),
+            config,
+            site,
+            asset::all((
+                blog::sitemap_urls(
+                    &paths.blog_src,
+                    &paths.blog_template,
+                    &paths.blog_out,
+                    config,
+                ),
+                raw::sitemap_urls(&paths.raw),
+            ))
+            .map(|(blog, raw)| {
+                let home = sitemap::Url {
+                    path: String::new(),
+                    modified: None,
+                };
+                let reviews = sitemap::Url {
+                    path: "reviews".to_owned(),
+                    modified: None,
+                };
+                [home, reviews].into_iter().chain(blog).chain(raw).collect()
+            }),
+        )
+        .catch_unwind(),
     ))
-    .map(|((), (), (), (), (), (), ())| {})
+    .map(|((), (), (), (), (), (), (), (), ())| {});
+
+    // Redirects are generated after everything else so that they know which paths are taken.
+    redirects::asset(&paths.redirects, Path::new(output), pages, config)
+        .catch_unwind()
+        // Everything may be minified, so the minifier must be initialized first.
+        .after(minify_init)
+}
+
+fn run_command(command: &str) -> anyhow::Result<()> {
+    let status = process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .status()
+        .with_context(|| format!("failed to run `{command}`"))?;
+    ensure!(status.success(), "`{command}` exited with {status}");
+    Ok(())
 }
 
 #[context("failed to set cwd to project root")]
@@ -213,9 +670,15 @@ use bumpalo::Bump;
 use crossbeam::channel;
 use fn_error_context::context;
 use notify::Watcher;
+use std::collections::HashSet;
 use std::env;
+use std::num::NonZeroUsize;
+use std::path::Component;
 use std::path::Path;
+use std::path::PathBuf;
+use std::process;
 use std::rc::Rc;
 use std::str;
+use std::sync::Arc;
 use std::time::Duration;
 use std::time::Instant;
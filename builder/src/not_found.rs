@@ -2,18 +2,26 @@ pub(crate) fn asset<'a>(
     template_path: &'a Path,
     output_path: &'a Path,
     templater: impl Asset<Output = Templater> + 'a,
+    config: impl Asset<Output = &'a Config> + 'a,
 ) -> impl Asset<Output = ()> + 'a {
     let template = asset::TextFile::new(template_path)
-        .map(|src| Template::compile(&src?).context("failed to compile 404 template"))
+        .and_then(|src| Template::compile(&src).context("failed to compile 404 template"))
         .map(Rc::new)
         .cache();
 
-    asset::all((templater, template))
-        .map(|(templater, template)| -> Result<String, ErrorPage> {
+    let html = asset::all((templater, template)).map(
+        |(templater, template)| -> Result<String, ErrorPage> {
             Ok(templater.render((*template).as_ref()?, ())?)
-        })
-        .map(move |html| {
-            write_file(output_path, html.unwrap_or_else(ErrorPage::into_html))?;
+        },
+    );
+
+    asset::all((html, config))
+        .map(move |(html, config)| {
+            write_file(
+                config,
+                output_path,
+                html.unwrap_or_else(ErrorPage::into_html),
+            )?;
             log::info!("successfully emitted 404 file");
             Ok(())
         })
@@ -21,6 +29,7 @@ pub(crate) fn asset<'a>(
         .modifies_path(output_path)
 }
 
+use crate::config::Config;
 use crate::templater::Templater;
 use crate::util::asset;
 use crate::util::asset::Asset;
@@ -1,9 +1,21 @@
-pub(crate) fn asset<'a>(src_dir: &'a Path, out_dir: &'a Path) -> impl Asset<Output = ()> + 'a {
+pub(crate) fn asset<'a>(
+    src_dir: &'a Path,
+    out_dir: &'a Path,
+    config: impl Asset<Output = &'a Config> + Copy + 'a,
+) -> impl Asset<Output = ()> + 'a {
     asset::Volatile
         .map(move |()| -> anyhow::Result<_> {
             let mut assets = Vec::new();
 
-            for entry in WalkDir::new(src_dir).follow_links(true) {
+            // Having no raw files, such as `CNAME` when there is no custom domain, is fine.
+            let walk_dir = src_dir
+                .exists()
+                .then(|| WalkDir::new(src_dir).follow_links(true));
+            if walk_dir.is_none() {
+                log::debug!("{} does not exist; no raw files to copy", src_dir.display());
+            }
+
+            for entry in walk_dir.into_iter().flatten() {
                 let entry = entry?;
                 if !entry.file_type().is_file() {
                     continue;
@@ -19,12 +31,14 @@ pub(crate) fn asset<'a>(src_dir: &'a Path, out_dir: &'a Path) -> impl Asset<Outp
                 let dest_0 = out_dir.join(relative);
                 let dest_1 = dest_0.clone();
 
-                let asset = asset::FsPath::new(src.clone())
-                    .map(move |()| {
-                        make_parents(&dest_0)?;
-                        fs::copy(&*src, &dest_0).with_context(|| {
-                            format!("failed to copy {} to {}", src.display(), dest_0.display())
-                        })?;
+                let is_cname = relative == Path::new("CNAME");
+
+                let asset = asset::all((asset::FsPath::new(src.clone()), config))
+                    .map(move |((), config)| {
+                        if is_cname {
+                            check_cname(&src);
+                        }
+                        copy_file(config, &src, &dest_0)?;
                         log::info!("Copied {} to {}", src.display(), dest_0.display());
                         Ok(())
                     })
@@ -48,10 +62,133 @@ pub(crate) fn asset<'a>(src_dir: &'a Path, out_dir: &'a Path) -> impl Asset<Outp
         .flatten()
 }
 
+/// The pages among the raw files, for the sitemap.
+pub(crate) fn sitemap_urls(src_dir: &Path) -> impl Asset<Output = Vec<sitemap::Url>> + '_ {
+    asset::Volatile
+        .map(move |()| {
+            // Errors are reported when copying the files.
+            WalkDir::new(src_dir)
+                .follow_links(true)
+                .sort_by_file_name()
+                .into_iter()
+                .flatten()
+                .filter(|entry| entry.file_type().is_file())
+                .filter_map(|entry| sitemap_path(entry.path().strip_prefix(src_dir).ok()?))
+                .map(|path| sitemap::Url {
+                    path,
+                    modified: None,
+                })
+                .collect::<Vec<_>>()
+        })
+        .dedup()
+}
+
+/// The path to list in the sitemap for a raw file, if it is a page.
+fn sitemap_path(relative: &Path) -> Option<String> {
+    if relative.extension() != Some("html".as_ref()) {
+        return None;
+    }
+    let components = relative
+        .components()
+        .map(|component| component.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>()?;
+    let path = components.join("/");
+    // Directories are served their `index.html`.
+    Some(match path.strip_suffix("index.html") {
+        Some(dir) if dir.is_empty() || dir.ends_with('/') => dir.to_owned(),
+        _ => path,
+    })
+}
+
+/// Warn if a GitHub Pages `CNAME` file looks malformed,
+/// since that would silently break the deployed site.
+fn check_cname(path: &Path) {
+    match fs::read_to_string(path) {
+        Ok(content) => {
+            if let Err(problem) = validate_cname(&content) {
+                log::warn!("{} looks malformed: {problem}", path.display());
+            }
+        }
+        Err(e) => log::warn!("failed to read {}: {e}", path.display()),
+    }
+}
+
+fn validate_cname(content: &str) -> Result<(), &'static str> {
+    let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+    let Some(hostname) = lines.next() else {
+        return Err("it is empty");
+    };
+    if lines.next().is_some() {
+        return Err("it should contain a single hostname");
+    }
+    let hostname = hostname.trim();
+    if hostname.contains("://") {
+        return Err("the hostname should not include a scheme");
+    }
+    if hostname.contains('/') {
+        return Err("the hostname should not include a path or trailing slash");
+    }
+    let valid_label = |label: &str| {
+        !label.is_empty()
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+    };
+    if !hostname.split('.').all(valid_label) {
+        return Err("it is not a valid hostname");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn cname() {
+        assert_eq!(validate_cname("example.com\n"), Ok(()));
+        assert_eq!(validate_cname("www.example.com"), Ok(()));
+        assert!(validate_cname("").is_err());
+        assert!(validate_cname("example.com\nwww.example.com\n").is_err());
+        assert!(validate_cname("https://example.com").is_err());
+        assert!(validate_cname("example.com/").is_err());
+        assert!(validate_cname("example..com").is_err());
+        assert!(validate_cname("exa mple.com").is_err());
+    }
+
+    #[test]
+    fn sitemap_paths() {
+        assert_eq!(sitemap_path(Path::new("CNAME")), None);
+        assert_eq!(
+            sitemap_path(Path::new("page.html")).as_deref(),
+            Some("page.html")
+        );
+        assert_eq!(
+            sitemap_path(Path::new("a/b.html")).as_deref(),
+            Some("a/b.html")
+        );
+        assert_eq!(sitemap_path(Path::new("index.html")).as_deref(), Some(""));
+        assert_eq!(
+            sitemap_path(Path::new("a/index.html")).as_deref(),
+            Some("a/")
+        );
+        assert_eq!(
+            sitemap_path(Path::new("aindex.html")).as_deref(),
+            Some("aindex.html")
+        );
+    }
+
+    use super::sitemap_path;
+    use super::validate_cname;
+    use std::path::Path;
+}
+
+use crate::config::Config;
+use crate::sitemap;
 use crate::util::asset;
 use crate::util::asset::Asset;
+use crate::util::copy_file;
 use crate::util::log_errors;
-use crate::util::make_parents;
 use anyhow::Context;
 use std::fs;
 use std::path::Path;
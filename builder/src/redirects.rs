@@ -0,0 +1,165 @@
+/// Redirects from the old paths of pages that have moved to their new locations,
+/// read from a TOML table of the form `"/old/path" = "/new/path"`.
+pub(crate) type Map = BTreeMap<String, String>;
+
+/// How redirects are emitted.
+#[derive(Clone, Copy, Default, Hash, clap::ValueEnum)]
+pub(crate) enum Format {
+    /// An HTML page at each old path that refreshes to the new one,
+    /// for hosts such as GitHub Pages which don't support redirects.
+    #[default]
+    Html,
+    /// A Netlify-style `_redirects` file.
+    Netlify,
+}
+
+pub(crate) fn parse(src: &str) -> anyhow::Result<Map> {
+    let map = toml::from_str::<Map>(src)?;
+    for from in map.keys() {
+        ensure!(
+            from.starts_with('/'),
+            "redirect from `{from}` is not an absolute path"
+        );
+        ensure!(
+            !from.split('/').any(|part| part.starts_with('.')),
+            "redirect from `{from}` has a component starting with `.`"
+        );
+    }
+    Ok(map)
+}
+
+/// Resolve the destination of a redirect against the path the site is hosted under.
+pub(crate) fn target(to: &str, base_path: &str) -> String {
+    match to.strip_prefix('/') {
+        Some(path) if !path.starts_with('/') => format!("{base_path}{path}"),
+        _ => to.to_owned(),
+    }
+}
+
+/// `others` is the rest of the site, whose files redirects mustn't overwrite.
+/// It is generated first, so that all of its output paths are known.
+pub(crate) fn asset<'a>(
+    path: &'a Path,
+    out_dir: &'a Path,
+    others: impl Asset<Output = ()> + 'a,
+    config: impl Asset<Output = &'a Config> + 'a,
+) -> impl Asset<Output = ()> + 'a {
+    let others = Rc::new(others);
+    asset::all((asset::TextFile::new(path), others.clone(), config))
+        .map(move |(src, (), config)| {
+            // Having no redirects is fine.
+            if !path.exists() {
+                log::debug!("{} does not exist; no redirects to emit", path.display());
+                return Ok(());
+            }
+            let mut map =
+                parse(&src?).with_context(|| format!("failed to read {}", path.display()))?;
+
+            match config.redirects_format {
+                Format::Html => {
+                    let mut taken = Vec::new();
+                    others.output_paths(&mut taken);
+                    let taken = taken.into_iter().collect::<HashSet<_>>();
+                    map.retain(|from, _| {
+                        let file_path = out_dir.join(html_path(from));
+                        let free = !taken.contains(&file_path);
+                        if !free {
+                            log::error!(
+                                "redirect from `{from}` would overwrite `{}`",
+                                file_path.display()
+                            );
+                        }
+                        free
+                    });
+                    for (from, to) in &map {
+                        let to = target(to, &config.base_path);
+                        write_file(config, out_dir.join(html_path(from)), refresh_page(&to))?;
+                    }
+                }
+                Format::Netlify => {
+                    let mut file = String::new();
+                    for (from, to) in &map {
+                        let from = target(from, &config.base_path);
+                        let to = target(to, &config.base_path);
+                        writeln!(file, "{from} {to} 301").unwrap();
+                    }
+                    write_file(config, out_dir.join("_redirects"), file)?;
+                }
+            }
+            log::info!("successfully emitted {} redirects", map.len());
+            Ok(())
+        })
+        .map(log_errors)
+}
+
+/// The file, relative to the output directory, that serves the given path.
+/// Paths not ending in `.html`, even if they have another extension like `/v1.2`,
+/// are served by the file with `.html` appended.
+fn html_path(from: &str) -> PathBuf {
+    let from = from.trim_start_matches('/');
+    if from.is_empty() || from.ends_with('/') {
+        Path::new(from).join("index.html")
+    } else if Path::new(from)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("html"))
+    {
+        PathBuf::from(from)
+    } else {
+        PathBuf::from(format!("{from}.html"))
+    }
+}
+
+fn refresh_page(to: &str) -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html><meta charset='utf-8'><title>Redirecting</title>");
+    html.push_str("<link rel='canonical' href='");
+    escape_href(&mut html, to);
+    html.push_str("'><meta http-equiv='refresh' content='0; url=");
+    escape_href(&mut html, to);
+    html.push_str("'><p>This page has moved to <a href='");
+    escape_href(&mut html, to);
+    html.push_str("'>");
+    escape_html(&mut html, to);
+    html.push_str("</a>.</p>");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn paths() {
+        assert_eq!(html_path("/blog/old"), Path::new("blog/old.html"));
+        assert_eq!(html_path("/blog/old/"), Path::new("blog/old/index.html"));
+        assert_eq!(html_path("/old.html"), Path::new("old.html"));
+        assert_eq!(html_path("/v1.2"), Path::new("v1.2.html"));
+        assert_eq!(html_path("/"), Path::new("index.html"));
+
+        assert_eq!(target("/blog/new", "/project/"), "/project/blog/new");
+        assert_eq!(target("https://a.com/", "/project/"), "https://a.com/");
+
+        assert!(parse(r#""/a" = "/b""#).is_ok());
+        assert!(parse(r#""a" = "/b""#).is_err());
+        assert!(parse(r#""/../a" = "/b""#).is_err());
+    }
+
+    use super::html_path;
+    use super::parse;
+    use super::target;
+    use std::path::Path;
+}
+
+use crate::config::Config;
+use crate::util::asset;
+use crate::util::asset::Asset;
+use crate::util::log_errors;
+use crate::util::push_str::escape_href;
+use crate::util::push_str::escape_html;
+use crate::util::write_file;
+use anyhow::ensure;
+use anyhow::Context as _;
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::rc::Rc;
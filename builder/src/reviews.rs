@@ -2,45 +2,67 @@ const HTML_PATH: &str = "reviews.html";
 const CSS_PATH: &str = "reviews.css";
 const JS_PATH: &str = "reviews.js";
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn asset<'a>(
     toml_path: &'a Path,
     template_path: &'a Path,
     css_path: &'a Path,
     js_path: &'a Path,
+    raw_dir: &'a Path,
     out_path: &'a Path,
     templater: impl Asset<Output = Templater> + Clone + 'a,
     config: impl Asset<Output = &'a Config> + Copy + 'a,
 ) -> impl Asset<Output = ()> + 'a {
     let template = asset::TextFile::new(template_path)
-        .map(|src| Template::compile(&src?).context("failed to compile reviews template"))
-        .map(Rc::new)
+        .and_then(|src| Template::compile(&src).context("failed to compile reviews template"))
+        .map(|res| res.map(Rc::new).map_err(Rc::new))
         .cache();
 
-    let template_vars = asset::TextFile::new(toml_path)
-        .map(|src| -> anyhow::Result<TemplateVars> {
+    let template_vars = asset::all((asset::TextFile::new(toml_path), config))
+        .map(|(src, config)| -> anyhow::Result<TemplateVars> {
             let data = toml::from_str::<Data>(&src?)?;
-            let introduction = markdown::parse(&data.introduction);
+            check_site_icons(&data.sites, &[raw_dir, out_path]);
+            let options = &config.markdown;
+            log::debug!("parsing Markdown in {}", toml_path.display());
+            let _context = PanicContext(format!("parsing Markdown in {}", toml_path.display()));
+            let introduction = markdown::parse(&data.introduction, options);
+            let stats = Stats::new(&data.entries);
+            let mut entries = data
+                .entries
+                .into_iter()
+                // Entries still to be reviewed are drafts.
+                .filter(|entry| config.drafts || entry.review.is_some())
+                .collect::<Vec<_>>();
+            // Newest releases first; the sort is stable so ties keep their order in the TOML.
+            entries.sort_by_key(|entry| Reverse(entry.released.start.first_day()));
             Ok(TemplateVars {
                 summary: introduction.summary,
                 introduction: introduction.body,
                 sites: data.sites,
-                entries: data.entries.into_iter().map(Entry::from).collect(),
+                entries: entries
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, entry)| Entry::from(entry, i, options))
+                    .collect(),
+                stats,
                 reviews_css: CSS_PATH,
                 reviews_js: JS_PATH,
             })
         })
-        .map(Rc::new)
+        .map(|res| res.map(Rc::new).map_err(Rc::new))
         .cache();
 
-    let html = asset::all((templater, template, template_vars))
-        .map(|(templater, template, template_vars)| {
-            let (template, template_vars) =
-                ErrorPage::zip((*template).as_ref(), (*template_vars).as_ref())?;
-            Ok(templater.render(template, template_vars)?)
-        })
-        .map(move |html| {
+    let html = asset::all((templater, template.zip(template_vars))).map(
+        |(templater, template_and_vars)| {
+            let (template, template_vars) = template_and_vars?;
+            Ok(templater.render(&template, &*template_vars)?)
+        },
+    );
+
+    let html = asset::all((html, config))
+        .map(move |(html, config)| {
             let html = html.unwrap_or_else(ErrorPage::into_html);
-            write_file(out_path.join(HTML_PATH), html)?;
+            write_file(config, out_path.join(HTML_PATH), html)?;
             log::info!("successfully emitted {HTML_PATH}");
             Ok(())
         })
@@ -64,21 +86,80 @@ pub(crate) fn asset<'a>(
     asset::all((html, css, js)).map(|((), (), ())| {})
 }
 
+/// Warn about sites whose icons can't be found in any of the given directories,
+/// since they would otherwise silently show up as broken images.
+fn check_site_icons(sites: &[data::Site], dirs: &[&Path]) {
+    for site in sites {
+        if site.icon.starts_with("//") || site.icon.contains("://") {
+            continue;
+        }
+        let icon = site.icon.trim_start_matches('/');
+        if !dirs.iter().any(|dir| dir.join(icon).is_file()) {
+            log::warn!("icon `{}` of site {} does not exist", site.icon, site.key);
+        }
+    }
+}
+
 #[derive(Serialize)]
 struct TemplateVars {
     summary: String,
     introduction: String,
     sites: Vec<data::Site>,
     entries: Vec<Entry>,
+    stats: Stats,
     reviews_css: &'static str,
     reviews_js: &'static str,
 }
 
+/// Summary statistics over every entry, including those not yet reviewed.
+#[derive(Serialize)]
+struct Stats {
+    entries: usize,
+    /// The mean score of the reviewed entries, rounded to a half step.
+    average_score: Option<&'static str>,
+    /// The number of entries of each form, leaving out forms with none.
+    forms: Vec<FormStats>,
+}
+
+#[derive(Serialize)]
+struct FormStats {
+    key: &'static str,
+    name: &'static str,
+    entries: usize,
+}
+
+impl Stats {
+    fn new(entries: &[data::Entry]) -> Self {
+        let scores = entries.iter().filter_map(|entry| entry.review.as_ref());
+        Self {
+            entries: entries.len(),
+            average_score: data::Score::mean(scores.map(|review| review.score))
+                .map(data::Score::as_str),
+            forms: data::Form::ALL
+                .into_iter()
+                .map(|form| FormStats {
+                    key: form.key(),
+                    name: form.name(),
+                    entries: entries
+                        .iter()
+                        .filter(|entry| entry.r#type.form() == form)
+                        .count(),
+                })
+                .filter(|form| form.entries != 0)
+                .collect(),
+        }
+    }
+}
+
 #[derive(Serialize)]
 struct Entry {
     r#type: &'static str,
+    /// The kebab-case form of the entry, such as `music-release`, used to filter by type.
+    form: &'static str,
     artists: String,
     title: String,
+    /// The ISO 8601 start of the release date, used to sort by release date.
+    released: String,
     released_short: String,
     released_full: String,
     genres: String,
@@ -87,7 +168,9 @@ struct Entry {
 }
 
 impl Entry {
-    fn from(entry: data::Entry) -> Self {
+    /// `index` is the entry's position on the page, which keeps the IDs in its comment unique.
+    fn from(entry: data::Entry, index: usize, options: &markdown::Options) -> Self {
+        let form = entry.r#type.form().key();
         let r#type = match entry.r#type {
             data::Type::MusicRelease(r) => {
                 macro_rules! match_recording_type {
@@ -135,18 +218,33 @@ impl Entry {
             data::Type::Film(data::r#type::Film::TvShow) => "TV show",
             data::Type::Film(data::r#type::Film::TvSeason) => "TV season",
             data::Type::VisualNovel => "Visual novel",
+            data::Type::Game(data::r#type::Game::Pc) => "PC game",
+            data::Type::Game(data::r#type::Game::Console) => "Console game",
+            data::Type::Game(data::r#type::Game::Mobile) => "Mobile game",
+            data::Type::Game(data::r#type::Game::Browser) => "Browser game",
+            data::Type::Podcast(data::r#type::Podcast::Episodic) => "Podcast",
+            data::Type::Podcast(data::r#type::Podcast::LimitedSeries) => "Limited series podcast",
         };
         Entry {
             r#type,
+            form,
             artists: entry.artists.join(", "),
             title: entry.title,
+            released: entry.released.start.to_string(),
             released_short: format!("{:#}", entry.released),
-            released_full: format!("{}", entry.released),
+            released_full: entry.released.human().to_string(),
             genres: entry.genres.join(", "),
             review: entry.review.map(|review| Review {
                 date: review.date.to_string(),
                 score: review.score.as_str(),
-                comment: review.comment.map(|c| markdown::parse(&c).body),
+                score_value: review.score.value(),
+                comment: review.comment.map(|c| {
+                    let options = markdown::Options {
+                        footnote_prefix: format!("review-{index}-"),
+                        ..options.clone()
+                    };
+                    markdown::parse(&c, &options).body
+                }),
             }),
             links: Some(entry.links).filter(|links| links.iter().any(Option::is_some)),
         }
@@ -157,6 +255,8 @@ impl Entry {
 struct Review {
     date: String,
     score: &'static str,
+    /// The score as a whole number from 0 to 10, used to sort by score.
+    score_value: u8,
     comment: Option<String>,
 }
 
@@ -217,10 +317,11 @@ mod data {
             fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
                 let mut sites = Vec::new();
                 let mut indices = HashMap::default();
-                while let Some((key, value)) = map.next_entry()? {
+                while let Some((key, mut value)) = map.next_entry::<String, Site>()? {
                     if indices.contains_key(&key) {
                         return Err(de::Error::custom(format_args!("duplicate site {key}")));
                     }
+                    value.key.clone_from(&key);
                     indices.insert(key, sites.len());
                     sites.push(value);
                 }
@@ -241,6 +342,9 @@ mod data {
     #[derive(Deserialize, Serialize)]
     #[serde(deny_unknown_fields)]
     pub(in crate::reviews) struct Site {
+        /// The key the site is referred to by in entries' links.
+        #[serde(skip)]
+        pub key: String,
         pub icon: String,
         pub alt: String,
     }
@@ -250,7 +354,7 @@ mod data {
             pub site_indices: &'sites HashMap<String, usize, S>,
         }
 
-        impl<'de, S: BuildHasher> serde::de::DeserializeSeed<'de> for DeserializeSeed<'_, S> {
+        impl<'de, S: BuildHasher> de::DeserializeSeed<'de> for DeserializeSeed<'_, S> {
             type Value = Vec<Entry>;
             fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
             where
@@ -304,7 +408,7 @@ mod data {
             pub site_indices: &'sites HashMap<String, usize, S>,
         }
 
-        impl<'de, S: BuildHasher> serde::de::DeserializeSeed<'de> for DeserializeSeed<'_, S> {
+        impl<'de, S: BuildHasher> de::DeserializeSeed<'de> for DeserializeSeed<'_, S> {
             type Value = Entry;
             fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
             where
@@ -411,6 +515,24 @@ mod data {
             Film(Film),
             /// A visual novel.
             VisualNovel,
+            /// A video game.
+            Game(Game),
+            /// A podcast.
+            Podcast(Podcast),
+        }
+
+        impl Type {
+            pub fn form(&self) -> Form {
+                match self {
+                    Type::MusicRelease(_) => Form::MusicRelease,
+                    Type::Comic(_) => Form::Comic,
+                    Type::Prose(_) => Form::Prose,
+                    Type::Film(_) => Form::Film,
+                    Type::VisualNovel => Form::VisualNovel,
+                    Type::Game(_) => Form::Game,
+                    Type::Podcast(_) => Form::Podcast,
+                }
+            }
         }
 
         impl<'de> Deserialize<'de> for Type {
@@ -441,18 +563,61 @@ mod data {
                     Form::Prose => Type::Prose(Prose::deserialize(deserializer)?),
                     Form::Film => Type::Film(<(Film,)>::deserialize(deserializer)?.0),
                     Form::VisualNovel => Type::VisualNovel,
+                    Form::Game => Type::Game(<(Game,)>::deserialize(deserializer)?.0),
+                    Form::Podcast => Type::Podcast(<(Podcast,)>::deserialize(deserializer)?.0),
                 })
             }
         }
 
-        #[derive(Deserialize)]
+        /// The broad kind of an entry, without the details of its `Type`.
+        #[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
         #[serde(rename_all = "kebab-case")]
-        enum Form {
+        pub(in crate::reviews) enum Form {
             MusicRelease,
             Comic,
             Prose,
             Film,
             VisualNovel,
+            Game,
+            Podcast,
+        }
+
+        impl Form {
+            pub const ALL: [Self; 7] = [
+                Form::MusicRelease,
+                Form::Comic,
+                Form::Prose,
+                Form::Film,
+                Form::VisualNovel,
+                Form::Game,
+                Form::Podcast,
+            ];
+
+            /// The name of the form in kebab-case, as written in the TOML.
+            pub const fn key(self) -> &'static str {
+                match self {
+                    Form::MusicRelease => "music-release",
+                    Form::Comic => "comic",
+                    Form::Prose => "prose",
+                    Form::Film => "film",
+                    Form::VisualNovel => "visual-novel",
+                    Form::Game => "game",
+                    Form::Podcast => "podcast",
+                }
+            }
+
+            /// The human-readable name of entries of the form, in the plural.
+            pub const fn name(self) -> &'static str {
+                match self {
+                    Form::MusicRelease => "Music",
+                    Form::Comic => "Comics",
+                    Form::Prose => "Prose",
+                    Form::Film => "Film",
+                    Form::VisualNovel => "Visual novels",
+                    Form::Game => "Games",
+                    Form::Podcast => "Podcasts",
+                }
+            }
         }
 
         pub(in crate::reviews) mod music_release {
@@ -539,6 +704,25 @@ mod data {
             TvSeason,
         }
 
+        /// The platform a game is primarily played on.
+        #[derive(Deserialize)]
+        #[serde(rename_all = "kebab-case")]
+        pub(in crate::reviews) enum Game {
+            Pc,
+            Console,
+            Mobile,
+            Browser,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(rename_all = "kebab-case")]
+        pub(in crate::reviews) enum Podcast {
+            /// An ongoing podcast with standalone episodes.
+            Episodic,
+            /// A podcast telling one story over a fixed number of episodes.
+            LimitedSeries,
+        }
+
         use serde::de;
         use serde::de::value::SeqAccessDeserializer;
         use serde::de::Deserializer;
@@ -546,6 +730,7 @@ mod data {
         use std::fmt;
         use std::fmt::Formatter;
     }
+    pub(in crate::reviews) use r#type::Form;
     pub(in crate::reviews) use r#type::Type;
 
     mod released {
@@ -572,6 +757,25 @@ mod data {
             }
         }
 
+        impl Released {
+            /// Display the release date in a human-readable form, like `March 2021 – May 2021`.
+            pub fn human(&self) -> impl Display + '_ {
+                struct Human<'a>(&'a Released);
+                impl Display for Human<'_> {
+                    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                        let Released { start, end } = *self.0;
+                        write!(f, "{}", start.human())?;
+                        match end {
+                            Some(end) if end == start => Ok(()),
+                            Some(end) => write!(f, " – {}", end.human()),
+                            None => f.write_str(" –"),
+                        }
+                    }
+                }
+                Human(self)
+            }
+        }
+
         impl<'de> Deserialize<'de> for Released {
             fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
                 deserializer.deserialize_str(DeVisitor)
@@ -579,7 +783,7 @@ mod data {
         }
 
         struct DeVisitor;
-        impl<'de> de::Visitor<'de> for DeVisitor {
+        impl de::Visitor<'_> for DeVisitor {
             type Value = Released;
             fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
                 f.write_str("a release date")
@@ -598,6 +802,11 @@ mod data {
                         })?),
                         None => Some(start),
                     };
+                if let Some(end) = end.filter(|end| end.is_before(start)) {
+                    return Err(de::Error::custom(format_args!(
+                        "end date {end} is before start date {start}"
+                    )));
+                }
                 Ok(Released { start, end })
             }
         }
@@ -680,6 +889,23 @@ mod data {
         pub(in crate::reviews) struct Score(u8);
 
         impl Score {
+            /// The mean of the given scores rounded to the nearest half step, with halfway
+            /// cases rounded up, or `None` if there are no scores.
+            pub fn mean(scores: impl IntoIterator<Item = Self>) -> Option<Self> {
+                let (sum, count) = scores
+                    .into_iter()
+                    .fold((0_usize, 0_usize), |(sum, count), score| {
+                        (sum + usize::from(score.0), count + 1)
+                    });
+                let mean = (sum * 2 + count).checked_div(count * 2)?;
+                Some(Score(u8::try_from(mean).unwrap()))
+            }
+
+            /// The score as a whole number from 0 to 10, twice the displayed value.
+            pub const fn value(self) -> u8 {
+                self.0
+            }
+
             pub const fn as_str(self) -> &'static str {
                 match self.0 {
                     0 => "0.0",
@@ -705,7 +931,7 @@ mod data {
         }
 
         struct DeVisitor;
-        impl<'de> de::Visitor<'de> for DeVisitor {
+        impl de::Visitor<'_> for DeVisitor {
             type Value = Score;
             fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
                 f.write_str("a score")
@@ -734,7 +960,7 @@ mod data {
         use std::fmt;
         use std::fmt::Formatter;
     }
-    use score::Score;
+    pub(in crate::reviews) use score::Score;
 
     mod links {
         pub(super) struct DeserializeSeed<'sites, S: BuildHasher> {
@@ -747,7 +973,7 @@ mod data {
             }
         }
 
-        impl<'de, S: BuildHasher> serde::de::DeserializeSeed<'de> for DeserializeSeed<'_, S> {
+        impl<'de, S: BuildHasher> de::DeserializeSeed<'de> for DeserializeSeed<'_, S> {
             type Value = Box<[Option<String>]>;
             fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
             where
@@ -795,6 +1021,114 @@ mod data {
     use std::fmt;
     use std::fmt::Formatter;
 }
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn stats() {
+        let data = toml::from_str::<Data>(
+            r#"
+            introduction = ""
+            [sites]
+            [[entries]]
+            type = ["film", "feature"]
+            artists = "A"
+            title = "A"
+            released = "2020"
+            genres = []
+            review.date = 2023-01-01
+            review.score = "3.0"
+            [[entries]]
+            type = ["film", "short"]
+            artists = "B"
+            title = "B"
+            released = "2021"
+            genres = []
+            review.date = 2023-01-01
+            review.score = "3.5"
+            [[entries]]
+            type = ["visual-novel"]
+            artists = "C"
+            title = "C"
+            released = "2022"
+            genres = []
+            review = "TODO"
+            "#,
+        )
+        .unwrap();
+        let stats = Stats::new(&data.entries);
+        assert_eq!(stats.entries, 3);
+        // The mean of 3.0 and 3.5 rounds up to 3.5; the unreviewed entry is not a 0.
+        assert_eq!(stats.average_score, Some("3.5"));
+        let forms = stats
+            .forms
+            .iter()
+            .map(|form| (form.key, form.entries))
+            .collect::<Vec<_>>();
+        assert_eq!(forms, [("film", 2), ("visual-novel", 1)]);
+
+        assert_eq!(Stats::new(&[]).average_score, None);
+    }
+
+    #[test]
+    fn types() {
+        let data = toml::from_str::<Data>(
+            r#"
+            introduction = ""
+            [sites]
+            [[entries]]
+            type = ["game", "pc"]
+            artists = "A"
+            title = "A"
+            released = "2020"
+            genres = []
+            review = "TODO"
+            [[entries]]
+            type = ["podcast", "limited-series"]
+            artists = "B"
+            title = "B"
+            released = "2021–2022"
+            genres = []
+            review = "TODO"
+            "#,
+        )
+        .unwrap();
+        let options = markdown::Options::default();
+        let types = data
+            .entries
+            .into_iter()
+            .map(|entry| {
+                let entry = Entry::from(entry, 0, &options);
+                (entry.form, entry.r#type)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            [("game", "PC game"), ("podcast", "Limited series podcast")]
+        );
+
+        let unknown_platform = r#"
+            introduction = ""
+            [sites]
+            [[entries]]
+            type = ["game", "arcade"]
+            artists = "A"
+            title = "A"
+            released = "2020"
+            genres = []
+            review = "TODO"
+        "#;
+        let Err(e) = toml::from_str::<Data>(unknown_platform) else {
+            panic!("unknown platform was accepted");
+        };
+        assert!(e.to_string().contains("arcade"), "{e}");
+    }
+
+    use super::Data;
+    use super::Entry;
+    use super::Stats;
+    use crate::util::markdown;
+}
+
 use data::Data;
 
 use crate::config::copy_minify;
@@ -807,8 +1141,10 @@ use crate::util::markdown;
 use crate::util::minify;
 use crate::util::write_file;
 use crate::util::ErrorPage;
+use crate::util::PanicContext;
 use anyhow::Context as _;
 use handlebars::Template;
 use serde::Serialize;
+use std::cmp::Reverse;
 use std::path::Path;
 use std::rc::Rc;
@@ -0,0 +1,39 @@
+pub(crate) fn asset<'a>(
+    output_path: &'a Path,
+    config: impl Asset<Output = &'a Config> + 'a,
+) -> impl Asset<Output = ()> + 'a {
+    config
+        .map(move |config| {
+            write_file(config, output_path, robots_txt(config))?;
+            log::info!("successfully emitted robots.txt");
+            Ok(())
+        })
+        .map(log_errors)
+        .modifies_path(output_path)
+}
+
+fn robots_txt(config: &Config) -> String {
+    let mut robots = String::from("User-agent: *\n");
+    if config.robots_disallow.is_empty() {
+        robots.push_str("Allow: /\n");
+    }
+    for path in &config.robots_disallow {
+        push!(robots, "Disallow: {path}\n");
+    }
+    // Sitemaps must be given by absolute URL.
+    if let Some(base_url) = &config.base_url {
+        push!(
+            robots,
+            "\nSitemap: {base_url}{}sitemap.xml\n",
+            config.base_path
+        );
+    }
+    robots
+}
+
+use crate::config::Config;
+use crate::util::asset::Asset;
+use crate::util::log_errors;
+use crate::util::push_str::push;
+use crate::util::write_file;
+use std::path::Path;
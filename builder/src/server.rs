@@ -4,29 +4,58 @@ pub(crate) struct Server {
 }
 
 impl Server {
-    pub(crate) fn new(path: &Path) -> Self {
+    /// Serve the directory at `path` under `base_path`, which starts and ends with `/`.
+    /// Requests for directories are served the file named `index` within them.
+    /// Paths listed in the redirects file are redirected unless a file exists at them,
+    /// as they would be in production.
+    /// Paths that match nothing are served the `fallback` file, relative to `path`,
+    /// with `fallback_status`.
+    pub(crate) fn new(
+        path: &Path,
+        base_path: &str,
+        index: &str,
+        redirects: &Path,
+        fallback: &Path,
+        fallback_status: http::StatusCode,
+    ) -> Self {
         Self {
             inner: Arc::from(Inner {
                 path: Box::from(path),
-                not_found_path: path.join("404.html"),
+                base_path: Box::from(base_path),
+                index: Box::from(index),
+                redirects_path: Box::from(redirects),
+                last_update: Mutex::new(None),
+                not_found_path: path.join(fallback),
+                not_found_status: fallback_status,
                 events: broadcast::channel(64).0,
             }),
         }
     }
 
+    /// Listen on the given port, or if it is taken, on one of the `attempts - 1` ports after it.
     #[context("failed to run server on port {port}")]
-    pub(crate) fn listen(&self, port: u16) -> anyhow::Result<Infallible> {
+    pub(crate) fn listen(&self, port: u16, attempts: u16) -> anyhow::Result<Infallible> {
         tokio::runtime::Builder::new_current_thread()
             .enable_all()
             .build()
             .context("failed to start tokio runtime")?
-            .block_on(self.listen_async(port))
+            .block_on(self.listen_async(port, attempts))
     }
 
-    async fn listen_async(&self, port: u16) -> anyhow::Result<Infallible> {
-        let listener = TcpListener::bind(("0.0.0.0", port))
-            .await
-            .context("failed to bind TCP listener")?;
+    async fn listen_async(&self, mut port: u16, attempts: u16) -> anyhow::Result<Infallible> {
+        let mut attempts_left = attempts;
+        let listener = loop {
+            attempts_left = attempts_left.saturating_sub(1);
+            match TcpListener::bind(("0.0.0.0", port)).await {
+                Ok(listener) => break listener,
+                Err(e) if e.kind() == io::ErrorKind::AddrInUse && attempts_left > 0 => {
+                    let next = port.checked_add(1).context("ran out of ports")?;
+                    log::warn!("port {port} is in use; trying port {next}");
+                    port = next;
+                }
+                Err(e) => return Err(anyhow!(e).context("failed to bind TCP listener")),
+            }
+        };
 
         log::info!("now listening on http://localhost:{port}");
 
@@ -58,6 +87,7 @@ impl Server {
     }
 
     pub(crate) fn update(&self, event: notify::Event) {
+        *self.inner.last_update.lock().unwrap() = Some(Utc::now());
         drop(self.inner.events.send(Arc::new(event)));
     }
 }
@@ -69,7 +99,13 @@ struct Service {
 
 struct Inner {
     path: Box<Path>,
+    base_path: Box<str>,
+    index: Box<str>,
+    redirects_path: Box<Path>,
     not_found_path: PathBuf,
+    not_found_status: http::StatusCode,
+    /// When the last file system event was seen.
+    last_update: Mutex<Option<DateTime<Utc>>>,
     events: broadcast::Sender<Arc<notify::Event>>,
 }
 
@@ -89,13 +125,50 @@ impl tower_service::Service<http::Request<hyper::Body>> for Service {
 
 impl Service {
     async fn respond(&self, req: http::Request<hyper::Body>) -> http::Response<hyper::Body> {
-        if req.uri().path() == "/watch" {
-            self.respond_sse(req).await
-        } else {
-            self.respond_file(req).await
+        match self.strip_base_path(req.uri().path()) {
+            Some("watch") => self.respond_sse(req).await,
+            Some("__status") => self.respond_status(),
+            _ => self.respond_file(req).await,
         }
     }
 
+    /// Look up where a path, relative to the base path, redirects to.
+    /// The redirects file is reread every time so that changes to it take effect immediately.
+    async fn redirect(&self, path: &str) -> Option<String> {
+        let inner = self.inner.clone();
+        let src = tokio::task::spawn_blocking(move || fs::read_to_string(&inner.redirects_path))
+            .await
+            .unwrap()
+            .ok()?;
+        let map = match redirects::parse(&src) {
+            Ok(map) => map,
+            Err(e) => {
+                log::error!("{:?}", e.context("failed to read redirects"));
+                return None;
+            }
+        };
+        // Redirects are written unencoded, like the paths of files.
+        let path = percent_encoding::percent_decode_str(path)
+            .decode_utf8()
+            .ok()?;
+        let to = map.get(&format!("/{path}"))?;
+        Some(redirects::target(to, &self.inner.base_path))
+    }
+
+    /// Respond with JSON describing the state of the server, for use by other tools.
+    fn respond_status(&self) -> http::Response<hyper::Body> {
+        let last_update = *self.inner.last_update.lock().unwrap();
+        let status = serde_json::json!({
+            "root": self.inner.path,
+            "last_update": last_update.map(|time| time.to_rfc3339()),
+        });
+        http::Response::builder()
+            .header("content-type", "application/json")
+            .header("cache-control", "no-store")
+            .body(hyper::Body::from(status.to_string()))
+            .unwrap()
+    }
+
     async fn respond_sse(&self, req: http::Request<hyper::Body>) -> http::Response<hyper::Body> {
         let mut paths = Vec::new();
         let Some(query) = req.uri().query() else {
@@ -105,41 +178,68 @@ impl Service {
             if key != "path" {
                 return bad_request("query key was not `path`");
             }
-            paths.push(match self.fs_path(&value).await {
-                Some((path, _metadata)) => path,
-                // TODO: Live-reload on the 404 page as well
-                None => return self.not_found().await,
-            });
+            if value.contains(['\r', '\n']) {
+                return bad_request("path contains a line break");
+            }
+            // TODO: Live-reload on the 404 page as well
+            let Some((path, _metadata)) = self.fs_path(&value).await else {
+                return self.not_found().await;
+            };
+            paths.push((value.into_owned(), path));
         }
 
         let (mut sender, body) = hyper::Body::channel();
 
         let mut receiver = self.inner.events.subscribe();
 
+        // Each message is the URL path of a changed file, or empty if it's unknown what changed.
+        // The client swaps changed stylesheets in place, so after those the stream is kept open
+        // to hear of later changes; anything else makes the client reload the page.
         tokio::spawn(async move {
             loop {
-                match receiver.recv().await {
-                    Ok(event) => {
-                        if event.paths.iter().any(|changed_path| {
-                            paths
+                let changed = match receiver.recv().await {
+                    Ok(event) => paths
+                        .iter()
+                        .filter(|(_, watched_path)| {
+                            event
+                                .paths
                                 .iter()
-                                .any(|watched_path| changed_path.ends_with(watched_path))
-                        }) {
-                            break;
-                        }
-                    }
+                                .any(|changed_path| changed_path.ends_with(watched_path))
+                        })
+                        .map(|(url_path, _)| &**url_path)
+                        .collect::<Vec<_>>(),
                     // Server shutdown; exit without reloading
                     Err(broadcast::error::RecvError::Closed) => return,
-                    Err(broadcast::error::RecvError::Lagged(_)) => break,
+                    Err(broadcast::error::RecvError::Lagged(_)) => vec![""],
+                };
+                if changed.is_empty() {
+                    continue;
                 }
-            }
-            if let Err(e) = sender.send_data("data:\n\n".into()).await {
-                // A closed channel is OK; it just means the client has disconnected.
-                if e.is_closed() {
+
+                let is_css = |path: &str| {
+                    Path::new(path)
+                        .extension()
+                        .is_some_and(|extension| extension.eq_ignore_ascii_case("css"))
+                };
+                let reload = changed.iter().copied().find(|path| !is_css(path));
+                let mut data = String::new();
+                for path in reload.map_or(changed, |path| vec![path]) {
+                    data.push_str("data: ");
+                    data.push_str(path);
+                    data.push_str("\n\n");
+                }
+                if let Err(e) = sender.send_data(data.into()).await {
+                    // A closed channel is OK; it just means the client has disconnected.
+                    if e.is_closed() {
+                        return;
+                    }
+                    let e = anyhow!(e).context("failed to send data to SSE stream");
+                    log::error!("{e:?}");
+                    return;
+                }
+                if reload.is_some() {
                     return;
                 }
-                let e = anyhow!(e).context("failed to send data to SSE stream");
-                log::error!("{e:?}");
             }
         });
 
@@ -151,6 +251,12 @@ impl Service {
 
     async fn respond_file(&self, req: http::Request<hyper::Body>) -> http::Response<hyper::Body> {
         let Some((path, metadata)) = self.fs_path(req.uri().path()).await else {
+            // Like on hosts such as Netlify, files that exist take precedence over redirects.
+            if let Some(path) = self.strip_base_path(req.uri().path()) {
+                if let Some(location) = self.redirect(path).await {
+                    return found(&location);
+                }
+            }
             return self.not_found().await;
         };
 
@@ -165,12 +271,36 @@ impl Service {
             _ => "application/octet-stream",
         };
 
-        let body = match *req.method() {
-            http::Method::HEAD => hyper::Body::empty(),
+        // With an ETag, browsers can revalidate files instead of downloading them again, which
+        // keeps live reloading fast for large images. Without one, don't let them cache at all.
+        let etag = etag(&metadata);
+        let if_none_match = req.headers().get("if-none-match");
+        if let (Some(etag), Some(if_none_match)) = (&etag, if_none_match) {
+            let if_none_match = if_none_match.to_str().unwrap_or("");
+            if matches!(*req.method(), http::Method::GET | http::Method::HEAD)
+                && etag_matches(if_none_match, etag)
+            {
+                return http::Response::builder()
+                    .status(http::StatusCode::NOT_MODIFIED)
+                    .header("etag", etag)
+                    .header("cache-control", "no-cache")
+                    .body(hyper::Body::empty())
+                    .unwrap();
+            }
+        }
+
+        // The length must match the body that a `GET` would send. Files are always sent
+        // unencoded, so for `HEAD` the file's size is exactly that; for `GET`, the file may have
+        // been rewritten since its metadata was read, so use the length of what was actually read.
+        let (body, content_length) = match *req.method() {
+            http::Method::HEAD => (hyper::Body::empty(), metadata.len()),
             http::Method::GET => {
                 let result = tokio::task::spawn_blocking(|| fs::read(path)).await;
                 match result.unwrap() {
-                    Ok(bytes) => hyper::Body::from(bytes),
+                    Ok(bytes) => {
+                        let len = bytes.len() as u64;
+                        (hyper::Body::from(bytes), len)
+                    }
                     Err(e) => {
                         log::error!("{:?}", anyhow!(e).context("failed to read file"));
                         return self.not_found().await;
@@ -180,16 +310,32 @@ impl Service {
             _ => return method_not_allowed(),
         };
 
-        http::Response::builder()
-            .header("content-length", metadata.len())
-            .header("content-type", content_type)
-            .header("cache-control", "no-store")
-            .body(body)
-            .unwrap()
+        let response = http::Response::builder()
+            .header("content-length", content_length)
+            .header("content-type", content_type);
+        match etag {
+            Some(etag) => response
+                .header("etag", etag)
+                .header("cache-control", "no-cache"),
+            None => response.header("cache-control", "no-store"),
+        }
+        .body(body)
+        .unwrap()
+    }
+
+    /// Strip the base path from a request path, leaving it relative to the served directory.
+    fn strip_base_path<'p>(&self, path: &'p str) -> Option<&'p str> {
+        let base_path = &*self.inner.base_path;
+        match path.strip_prefix(base_path) {
+            Some(path) => Some(path),
+            // Allow the base path to be requested without its trailing slash.
+            None if path == &base_path[..base_path.len() - 1] => Some(""),
+            None => None,
+        }
     }
 
     async fn fs_path(&self, path: &str) -> Option<(PathBuf, fs::Metadata)> {
-        let path = path.trim_start_matches('/');
+        let path = self.strip_base_path(path)?.trim_start_matches('/');
         let decoded = percent_encoding::percent_decode_str(path)
             .decode_utf8()
             .ok()?;
@@ -206,10 +352,11 @@ impl Service {
             return None;
         }
 
+        let inner = self.inner.clone();
         let task = tokio::task::spawn_blocking(move || {
             let metadata = match fs::metadata(&*path) {
                 Ok(metadata) if !metadata.is_file() => {
-                    path.push("index.html");
+                    path.push(&*inner.index);
                     fs::metadata(&*path)?
                 }
                 Ok(metadata) => metadata,
@@ -225,7 +372,7 @@ impl Service {
     }
 
     async fn not_found(&self) -> http::Response<hyper::Body> {
-        let response = http::Response::builder().status(http::StatusCode::NOT_FOUND);
+        let response = http::Response::builder().status(self.inner.not_found_status);
 
         let inner = self.inner.clone();
         match tokio::task::spawn_blocking(move || fs::read(&inner.not_found_path)).await {
@@ -238,6 +385,25 @@ impl Service {
     }
 }
 
+/// An entity tag for a file, derived from its modification time and length, which both change
+/// whenever the builder rewrites it.
+fn etag(metadata: &fs::Metadata) -> Option<String> {
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(format!(
+        "\"{:x}-{:x}\"",
+        modified.as_nanos(),
+        metadata.len()
+    ))
+}
+
+/// Whether an `If-None-Match` header matches an entity tag, using the weak comparison it calls for.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let etag = etag.strip_prefix("W/").unwrap_or(etag);
+    if_none_match.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
+
 fn bad_request(err: impl Display) -> http::Response<hyper::Body> {
     let mut bytes = BytesMut::new();
     write!((&mut bytes).writer(), "{err}").unwrap();
@@ -247,6 +413,16 @@ fn bad_request(err: impl Display) -> http::Response<hyper::Body> {
         .unwrap()
 }
 
+// Not a permanent redirect, since browsers cache those indefinitely,
+// which would get in the way of editing the redirects.
+fn found(location: &str) -> http::Response<hyper::Body> {
+    http::Response::builder()
+        .status(http::StatusCode::FOUND)
+        .header("location", location)
+        .body(hyper::Body::empty())
+        .unwrap()
+}
+
 fn method_not_allowed() -> http::Response<hyper::Body> {
     http::Response::builder()
         .status(http::StatusCode::METHOD_NOT_ALLOWED)
@@ -260,10 +436,27 @@ const CONNECTION_ERROR_KINDS: [io::ErrorKind; 3] = [
     io::ErrorKind::ConnectionReset,
 ];
 
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn if_none_match() {
+        assert!(etag_matches("\"1-2\"", "\"1-2\""));
+        assert!(etag_matches("\"0-0\", W/\"1-2\"", "\"1-2\""));
+        assert!(etag_matches("*", "\"1-2\""));
+        assert!(!etag_matches("\"1-3\"", "\"1-2\""));
+        assert!(!etag_matches("", "\"1-2\""));
+    }
+
+    use super::etag_matches;
+}
+
+use crate::redirects;
 use anyhow::anyhow;
 use anyhow::Context as _;
 use bytes::BufMut as _;
 use bytes::BytesMut;
+use chrono::DateTime;
+use chrono::Utc;
 use fn_error_context::context;
 use hyper::http;
 use std::convert::Infallible;
@@ -277,7 +470,9 @@ use std::path::Path;
 use std::path::PathBuf;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::task;
 use std::task::Poll;
+use std::time::UNIX_EPOCH;
 use tokio::net::TcpListener;
 use tokio::sync::broadcast;
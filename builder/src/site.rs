@@ -0,0 +1,37 @@
+/// Metadata about the site as a whole, available to every template as `site`.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Metadata {
+    /// The site's name, used for example as the suffix of page titles.
+    pub title: String,
+    /// The name of the site's author.
+    pub author: String,
+    /// The URL the site is hosted at, without a trailing slash.
+    pub url: String,
+    /// A short description of the site.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+pub(crate) fn asset(path: &Path) -> impl Asset<Output = Rc<Metadata>> + '_ {
+    asset::TextFile::new(path)
+        .and_then(|src| {
+            serde_json::from_str::<Metadata>(&src)
+                .with_context(|| format!("failed to read {}", path.display()))
+        })
+        .map(|res| {
+            Rc::new(res.unwrap_or_else(|e| {
+                log::error!("{e:?}");
+                Metadata::default()
+            }))
+        })
+        .cache()
+}
+
+use crate::util::asset;
+use crate::util::asset::Asset;
+use anyhow::Context as _;
+use serde::Deserialize;
+use serde::Serialize;
+use std::path::Path;
+use std::rc::Rc;
@@ -0,0 +1,89 @@
+//! `sitemap.xml`, listing the site's pages for search engines.
+
+/// A page to list in the sitemap.
+#[derive(Clone, PartialEq)]
+pub(crate) struct Url {
+    /// The page's path relative to the site's base path, such as `blog/some-post`.
+    pub path: String,
+    /// When the page's content last changed, if known.
+    pub modified: Option<NaiveDate>,
+}
+
+pub(crate) fn asset<'a>(
+    output_path: &'a Path,
+    config: impl Asset<Output = &'a Config> + 'a,
+    site: impl Asset<Output = Rc<site::Metadata>> + 'a,
+    urls: impl Asset<Output = Vec<Url>> + 'a,
+) -> impl Asset<Output = ()> + 'a {
+    asset::all((config, site, urls))
+        .map(move |(config, site, urls)| {
+            // Sitemaps must be given absolute URLs.
+            let root = match &config.base_url {
+                Some(base_url) => format!("{base_url}{}", config.base_path),
+                None => format!("{}/", site.url),
+            };
+            write_file(config, output_path, build_sitemap(&root, &urls))?;
+            log::info!("successfully emitted sitemap");
+            Ok(())
+        })
+        .map(log_errors)
+        .modifies_path(output_path)
+}
+
+fn build_sitemap(root: &str, urls: &[Url]) -> String {
+    let mut sitemap = String::from("<?xml version='1.0' encoding='UTF-8'?>");
+    sitemap.push_str("<urlset xmlns='http://www.sitemaps.org/schemas/sitemap/0.9'>");
+    for url in urls {
+        sitemap.push_str("<url><loc>");
+        escape_href(&mut sitemap, &format!("{root}{}", url.path));
+        sitemap.push_str("</loc>");
+        if let Some(modified) = url.modified {
+            push!(sitemap, "<lastmod>{modified}</lastmod>");
+        }
+        sitemap.push_str("</url>");
+    }
+    sitemap.push_str("</urlset>");
+    sitemap
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn sitemap() {
+        let urls = [
+            Url {
+                path: String::new(),
+                modified: None,
+            },
+            Url {
+                path: "blog/a post&more".to_owned(),
+                modified: NaiveDate::from_ymd_opt(2022, 3, 24),
+            },
+        ];
+        assert_eq!(
+            build_sitemap("https://example.com/", &urls),
+            "<?xml version='1.0' encoding='UTF-8'?>\
+            <urlset xmlns='http://www.sitemaps.org/schemas/sitemap/0.9'>\
+            <url><loc>https://example.com/</loc></url>\
+            <url><loc>https://example.com/blog/a%20post&amp;more</loc>\
+            <lastmod>2022-03-24</lastmod></url>\
+            </urlset>"
+        );
+    }
+
+    use super::build_sitemap;
+    use super::Url;
+    use chrono::NaiveDate;
+}
+
+use crate::config::Config;
+use crate::site;
+use crate::util::asset;
+use crate::util::asset::Asset;
+use crate::util::log_errors;
+use crate::util::push_str::escape_href;
+use crate::util::push_str::push;
+use crate::util::write_file;
+use chrono::NaiveDate;
+use std::path::Path;
+use std::rc::Rc;
@@ -2,55 +2,195 @@
 pub(crate) struct Templater {
     handlebars: Rc<Handlebars<'static>>,
     live_reload: bool,
-    icons: bool,
-    minify: bool,
+    icons: Option<Rc<icons::Links>>,
+    minify: Option<minify::Options>,
+    lang: Rc<str>,
+    dir: &'static str,
+    base_path: Rc<str>,
+    base_url: Rc<str>,
+    site: Rc<site::Metadata>,
+    manifest: Arc<Manifest>,
 }
 
 impl Templater {
-    #[context("failed to render template")]
     pub(crate) fn render(
         &self,
         template: &Template,
         vars: impl Serialize,
+    ) -> anyhow::Result<String> {
+        self.render_with_css(template, vars, &[])
+    }
+
+    /// The URL of an asset, given its logical path relative to the output directory.
+    pub(crate) fn asset_url(&self, path: &str) -> String {
+        let path = self.manifest.get(path).map_or(path, String::as_str);
+        format!("{}{path}", self.base_path)
+    }
+
+    /// Render a template for a page that depends on stylesheets beyond the common CSS.
+    /// They are passed to the template alongside the common CSS as `critical_css`,
+    /// so that it can emit preload hints for them.
+    #[context("failed to render template")]
+    pub(crate) fn render_with_css(
+        &self,
+        template: &Template,
+        vars: impl Serialize,
+        page_css: &[&str],
     ) -> anyhow::Result<String> {
         #[derive(Serialize)]
-        struct TemplateVars<T> {
+        struct TemplateVars<'a, T> {
             #[serde(flatten)]
             rest: T,
-            icons: Option<icons::Paths>,
-            common_css: &'static str,
+            icons: Option<&'a icons::Links>,
+            common_css: &'a str,
             live_reload: bool,
+            lang: &'a str,
+            dir: &'static str,
+            /// The path the site is hosted under, to be prepended to root-relative links.
+            base: &'a str,
+            /// The origin the site is hosted at, to be prepended to `base` to make absolute URLs.
+            /// Empty if unknown, leaving URLs relative.
+            base_url: &'a str,
+            site: &'a site::Metadata,
+            critical_css: Vec<String>,
         }
 
+        let common_css = self
+            .manifest
+            .get(common_css::PATH)
+            .map_or(common_css::PATH, String::as_str);
+        let mut critical_css = vec![self.asset_url(common_css::PATH)];
+        critical_css.extend(page_css.iter().map(|&path| path.to_owned()));
+
         let vars = TemplateVars {
             rest: vars,
-            icons: self.icons.then_some(icons::PATHS),
-            common_css: common_css::PATH,
+            icons: self.icons.as_deref(),
+            common_css,
             live_reload: self.live_reload,
+            lang: &self.lang,
+            dir: self.dir,
+            base: &self.base_path,
+            base_url: &self.base_url,
+            site: &self.site,
+            critical_css,
         };
         let context = handlebars::Context::wraps(vars).unwrap();
 
-        let mut render_context = handlebars::RenderContext::new(None);
+        let mut render_context = RenderContext::new(None);
         let mut rendered = template.renders(&self.handlebars, &context, &mut render_context)?;
-        if self.minify {
-            minify(minify::FileType::Html, &mut rendered);
+        if let Some(options) = self.minify {
+            minify(options, minify::FileType::Html, &mut rendered);
         }
         Ok(rendered)
     }
 }
 
+/// Mapping from the logical paths of assets to the paths they are actually emitted at,
+/// both relative to the output directory.
+pub(crate) type Manifest = HashMap<String, String>;
+
+/// The `{{asset "path"}}` helper, which resolves a logical asset path using the manifest.
+struct AssetHelper {
+    manifest: Arc<Manifest>,
+}
+
+impl HelperDef for AssetHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        helper: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc handlebars::Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let name = helper
+            .param(0)
+            .and_then(|param| param.value().as_str())
+            .ok_or_else(|| RenderError::new("`asset` helper expects a string parameter"))?;
+        let path = self
+            .manifest
+            .get(name)
+            .ok_or_else(|| RenderError::new(format!("unknown asset `{name}`")))?;
+        out.write(path)?;
+        Ok(())
+    }
+}
+
+/// The `{{date "2021-03"}}` helper, which displays an ISO 8601 date of any precision
+/// in a human-readable form.
+/// Given a `chrono` format as well, like `{{date "2021-03-05" "%B %-d, %Y"}}`,
+/// it displays a full date in that format instead.
+/// Invalid dates and formats are shown as inline errors rather than failing the whole page.
+struct DateHelper;
+
+impl HelperDef for DateHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        helper: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc handlebars::Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let date = helper
+            .param(0)
+            .and_then(|param| param.value().as_str())
+            .ok_or_else(|| RenderError::new("`date` helper expects a string parameter"))?;
+        let format = match helper.param(1) {
+            Some(param) => Some(param.value().as_str().ok_or_else(|| {
+                RenderError::new("`date` helper expects its format to be a string")
+            })?),
+            None => None,
+        };
+        match format_date(date, format) {
+            Ok(formatted) => out.write(&formatted)?,
+            Err(e) => {
+                log::error!("{e}");
+                let mut html = String::from("<span style='color:red'>Error: ");
+                escape_html(&mut html, &e);
+                html.push_str("</span>");
+                out.write(&html)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn format_date(date: &str, format: Option<&str>) -> Result<String, String> {
+    let date = date.parse::<PrecisionDate>().map_err(|e| e.to_string())?;
+    let Some(format) = format else {
+        return Ok(date.human().to_string());
+    };
+    let PrecisionDate::Day(date) = date else {
+        return Err(format!("date {date} has no day to format"));
+    };
+    // `chrono` only reports an invalid format once it's being written.
+    let mut formatted = String::new();
+    write!(formatted, "{}", date.format(format))
+        .map_err(|_| format!("invalid date format `{format}`"))?;
+    Ok(formatted)
+}
+
 thread_local! {
     static FALLBACK_TEMPLATER: Templater = Templater {
         handlebars: Rc::new(Handlebars::new()),
         // This value doesn't matter since we haven't included templates that reference it
         live_reload: false,
-        icons: false,
-        minify: false,
+        icons: None,
+        minify: None,
+        lang: Rc::from("en"),
+        dir: "ltr",
+        base_path: Rc::from("/"),
+        base_url: Rc::from(""),
+        site: Rc::default(),
+        manifest: Arc::new(Manifest::new()),
     };
 }
 
 pub(crate) fn asset<'a>(
     include_dir: &'a Path,
+    manifest: impl Asset<Output = Arc<Manifest>> + Clone + 'a,
+    site: impl Asset<Output = Rc<site::Metadata>> + Clone + 'a,
     config: impl Asset<Output = &'a Config> + Copy + 'a,
 ) -> impl Asset<Output = Templater> + 'a {
     asset::Dir::new(include_dir)
@@ -82,20 +222,35 @@ pub(crate) fn asset<'a>(
                 includes.push(include);
             }
 
-            Ok(asset::all((config, asset::all(includes)))
-                .map(|(config, includes)| {
-                    let mut handlebars = Handlebars::new();
-                    for (name, include) in Vec::from(includes).into_iter().flatten() {
-                        handlebars.register_template(&name, include);
-                    }
-                    Templater {
-                        handlebars: Rc::new(handlebars),
-                        icons: config.icons,
-                        live_reload: config.live_reload,
-                        minify: config.minify,
-                    }
-                })
-                .cache())
+            Ok(
+                asset::all((config, asset::all(includes), manifest.clone(), site.clone()))
+                    .map(|(config, includes, manifest, site)| {
+                        let mut handlebars = Handlebars::new();
+                        for (name, include) in Vec::from(includes).into_iter().flatten() {
+                            handlebars.register_template(&name, include);
+                        }
+                        handlebars.register_helper(
+                            "asset",
+                            Box::new(AssetHelper {
+                                manifest: manifest.clone(),
+                            }),
+                        );
+                        handlebars.register_helper("date", Box::new(DateHelper));
+                        Templater {
+                            handlebars: Rc::new(handlebars),
+                            icons: icons::Links::new(config).map(Rc::new),
+                            live_reload: config.live_reload,
+                            minify: config.minify_options(),
+                            lang: Rc::from(&*config.lang),
+                            dir: config.dir(),
+                            base_path: Rc::from(&*config.base_path),
+                            base_url: Rc::from(config.base_url.as_deref().unwrap_or("")),
+                            site,
+                            manifest,
+                        }
+                    })
+                    .cache(),
+            )
         })
         .map(|res| -> Rc<dyn Asset<Output = _>> {
             match res {
@@ -112,18 +267,95 @@ pub(crate) fn asset<'a>(
         .flatten()
 }
 
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn asset_helper() {
+        let manifest = Manifest::from([("common.css".to_owned(), "common.abc123.css".to_owned())]);
+        let mut handlebars = Handlebars::new();
+        handlebars.register_helper(
+            "asset",
+            Box::new(AssetHelper {
+                manifest: Arc::new(manifest),
+            }),
+        );
+
+        let rendered = handlebars.render_template("<link href='/{{asset \"common.css\"}}'>", &());
+        assert_eq!(rendered.unwrap(), "<link href='/common.abc123.css'>");
+
+        let error = handlebars
+            .render_template("{{asset \"common.cs\"}}", &())
+            .unwrap_err();
+        assert!(error.to_string().contains("unknown asset `common.cs`"));
+
+        assert!(handlebars.render_template("{{asset}}", &()).is_err());
+    }
+
+    #[test]
+    fn date_helper() {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_helper("date", Box::new(DateHelper));
+        let render = |date| handlebars.render_template(&format!("{{{{date \"{date}\"}}}}"), &());
+
+        assert_eq!(render("2021").unwrap(), "2021");
+        assert_eq!(render("2021-03").unwrap(), "March 2021");
+        assert_eq!(render("2021-03-05").unwrap(), "5 March 2021");
+        assert_eq!(
+            render("2021-13").unwrap(),
+            "<span style='color:red'>Error: invalid date: month 13 is not in the range [1, 12]</span>"
+        );
+    }
+
+    #[test]
+    fn date_helper_format() {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_helper("date", Box::new(DateHelper));
+        let render = |date, format| {
+            handlebars.render_template(&format!("{{{{date \"{date}\" \"{format}\"}}}}"), &())
+        };
+
+        assert_eq!(render("2021-03-05", "%B %-d, %Y").unwrap(), "March 5, 2021");
+        assert_eq!(
+            render("2021-03", "%B %-d, %Y").unwrap(),
+            "<span style='color:red'>Error: date 2021-03 has no day to format</span>"
+        );
+        assert_eq!(
+            render("2021-03-05", "%Q").unwrap(),
+            "<span style='color:red'>Error: invalid date format `%Q`</span>"
+        );
+    }
+
+    use super::AssetHelper;
+    use super::DateHelper;
+    use super::Manifest;
+    use handlebars::Handlebars;
+    use std::sync::Arc;
+}
+
 use crate::common_css;
 use crate::config::Config;
 use crate::icons;
+use crate::site;
 use crate::util::asset;
 use crate::util::asset::Asset;
 use crate::util::minify;
 use crate::util::minify::minify;
+use crate::util::precision_date::PrecisionDate;
+use crate::util::push_str::escape_html;
 use anyhow::Context as _;
 use fn_error_context::context;
 use handlebars::template::Template;
 use handlebars::Handlebars;
+use handlebars::Helper;
+use handlebars::HelperDef;
+use handlebars::HelperResult;
+use handlebars::Output;
+use handlebars::RenderContext;
+use handlebars::RenderError;
 use handlebars::Renderable as _;
 use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write as _;
 use std::path::Path;
 use std::rc::Rc;
+use std::sync::Arc;
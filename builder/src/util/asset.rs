@@ -11,6 +11,25 @@ pub(crate) trait Asset {
     /// Generate the asset's value.
     fn generate(&self) -> Self::Output;
 
+    /// Collect the paths of the files that generating this asset may write to.
+    ///
+    /// Assets that don't write files needn't implement this;
+    /// combinators forward it to the assets they contain.
+    fn output_paths(&self, _paths: &mut Vec<PathBuf>) {}
+
+    /// Start any work that generating this asset will need on the thread pool,
+    /// so that it happens at the same time as that of other assets.
+    ///
+    /// [`all`] does this for each of its assets before generating any of them.
+    /// Combinators forward it to the assets they contain when those would be generated.
+    fn prefetch(&self) {}
+
+    /// Get the asset's value if it is already known without generating anything,
+    /// such as when it is cached and up to date.
+    fn peek(&self) -> Option<Self::Output> {
+        None
+    }
+
     fn map<O, F: Fn(Self::Output) -> O>(self, f: F) -> Map<Self, F>
     where
         Self: Sized,
@@ -18,6 +37,39 @@ pub(crate) trait Asset {
         Map::new(self, f)
     }
 
+    /// Like [`map`](Self::map), but once the asset is prefetched `f` runs on the thread pool
+    /// set up by [`init_thread_pool`], if there is one.
+    fn par_map<O, F>(self, f: F) -> ParMap<Self, F, O>
+    where
+        Self: Sized,
+        Self::Output: Send + 'static,
+        F: Fn(Self::Output) -> O + Send + Sync + 'static,
+        O: Send + 'static,
+    {
+        ParMap::new(self, f)
+    }
+
+    /// Map the success value of an asset that outputs a `Result`, passing errors through.
+    fn and_then<T, E, O, F>(self, f: F) -> AndThen<Self, F>
+    where
+        Self: Asset<Output = Result<T, E>> + Sized,
+        F: Fn(T) -> Result<O, E>,
+    {
+        AndThen::new(self, f)
+    }
+
+    /// Combine this asset with another, where both output `Result`s, into an asset that outputs
+    /// both of their success values, or an error page showing the errors of those that failed.
+    fn zip<B, T0, E0, T1, E1>(self, other: B) -> Zip<Self, B>
+    where
+        Self: Asset<Output = Result<T0, E0>> + Sized,
+        B: Asset<Output = Result<T1, E1>>,
+        E0: Borrow<anyhow::Error>,
+        E1: Borrow<anyhow::Error>,
+    {
+        Zip::new(self, other)
+    }
+
     fn flatten(self) -> Flatten<Self>
     where
         Self: Sized,
@@ -26,6 +78,26 @@ pub(crate) trait Asset {
         Flatten::new(self)
     }
 
+    /// Pair the output of this asset with the time at which it was last modified.
+    fn with_modified(self) -> WithModified<Self>
+    where
+        Self: Sized,
+    {
+        WithModified::new(self)
+    }
+
+    /// Generate `dependency` before this asset every time this asset is generated.
+    ///
+    /// This is for dependencies whose side effects this asset relies on, such as initializing
+    /// global state, making the order explicit rather than relying on the order in [`all`].
+    fn after<D>(self, dependency: D) -> After<Self, D>
+    where
+        Self: Sized,
+        D: Asset<Output = ()>,
+    {
+        After::new(self, dependency)
+    }
+
     /// Cache the result of this asset.
     fn cache(self) -> Cache<Self>
     where
@@ -35,6 +107,50 @@ pub(crate) trait Asset {
         Cache::new(self)
     }
 
+    /// Cache the result of this asset, but only consider it modified when its output changes,
+    /// so that what depends on it isn't regenerated when it comes out the same.
+    ///
+    /// Checking whether it has been modified generates the inner asset if that has been,
+    /// so it should be cheap.
+    fn dedup(self) -> Dedup<Self>
+    where
+        Self: Sized,
+        Self::Output: Clone + PartialEq,
+    {
+        Dedup::new(self)
+    }
+
+    /// Cache the result of this asset both in memory and on disk under `key`,
+    /// so that later runs of the builder can reuse it if it hasn't been modified since.
+    ///
+    /// Entries on disk are only reused by the same executable run with the same config,
+    /// since those may affect the output too. Without [`init_disk_cache`], this is just [`cache`].
+    ///
+    /// [`cache`]: Self::cache
+    fn persist_cache(self, key: String) -> PersistCache<Self>
+    where
+        Self: Sized,
+        Self::Output: Clone + Serialize + DeserializeOwned,
+    {
+        PersistCache::new(self, key)
+    }
+
+    /// Map each key output by this asset to a value, typically an asset for that key,
+    /// reusing the value made for the same key on a previous generation.
+    ///
+    /// Values for keys that are no longer output are kept in case they come back,
+    /// up to `capacity` of them, after which the least recently used are dropped.
+    fn cache_each<K, V, F>(self, capacity: usize, f: F) -> CacheEach<Self, K, V, F>
+    where
+        Self: Sized,
+        Self::Output: IntoIterator<Item = K>,
+        K: Hash + Eq + Clone,
+        V: Clone,
+        F: Fn(&K) -> V,
+    {
+        CacheEach::new(self, capacity, f)
+    }
+
     /// Cache the output of the asset based on the fact that it modifies a certain path.
     ///
     /// `to_file` already does this caching, so it's not necessary to apply after that.
@@ -44,9 +160,20 @@ pub(crate) trait Asset {
     {
         ModifiesPath::new(self, path)
     }
+
+    /// Log panics that occur while generating this asset instead of propagating them,
+    /// so that the rest of the site still builds.
+    ///
+    /// After a panic, inner [`Cache`]s are left empty and so will regenerate next time.
+    fn catch_unwind(self) -> CatchUnwind<Self>
+    where
+        Self: Asset<Output = ()> + Sized,
+    {
+        CatchUnwind::new(self)
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Serialize, Deserialize)]
 pub(crate) enum Modified {
     Never,
     At(SystemTime),
@@ -60,6 +187,161 @@ impl Modified {
             .map(Self::At)
             .ok()
     }
+
+    /// Like [`Self::path`], but with [`use_content_hashes`] this is instead when the file's
+    /// contents were first seen, so that it only changes when they do.
+    ///
+    /// Hashes aren't a variant of `Modified` themselves, because modification times are combined
+    /// by taking the latest and compared with those of outputs in [`ModifiesPath`],
+    /// and hashes can't be ordered like that. The time they were first seen can.
+    ///
+    /// Files are only read again once their modification time or size changes,
+    /// or [`rehash`] is called on them.
+    fn contents<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let path = path.as_ref();
+        let metadata = path.symlink_metadata().ok()?;
+        let mtime = metadata.modified().ok()?;
+        if !CONTENT_HASHES.load(atomic::Ordering::Relaxed) {
+            return Some(Self::At(mtime));
+        }
+        let stat = (mtime, metadata.len());
+        let last_hashed = BUILD_CACHE.lock().unwrap().hashed.get(path).copied();
+        let hash = match last_hashed {
+            Some((hashed_stat, hash)) if hashed_stat == stat => hash,
+            _ => {
+                let Ok(contents) = fs::read(path) else {
+                    return Some(Self::At(mtime));
+                };
+                let mut hasher = DefaultHasher::new();
+                contents.hash(&mut hasher);
+                let hash = hasher.finish();
+                let mut cache = BUILD_CACHE.lock().unwrap();
+                cache.hashed.insert(path.to_owned(), (stat, hash));
+                hash
+            }
+        };
+        let mut seen = BUILD_CACHE.lock().unwrap();
+        let seen = seen.files.entry(path.to_owned()).or_insert(SeenContents {
+            hash,
+            modified: mtime,
+        });
+        Some(Self::At(seen.update(hash, SystemTime::now())))
+    }
+}
+
+static CONTENT_HASHES: AtomicBool = AtomicBool::new(false);
+
+static BUILD_CACHE: Lazy<Mutex<BuildCache>> = Lazy::new(Mutex::default);
+
+/// Where [`BUILD_CACHE`] is kept between runs.
+static BUILD_CACHE_PATH: OnceCell<PathBuf> = OnceCell::new();
+
+/// Consider files read by [`FsPath`] and [`TextFile`] modified only when their contents change,
+/// rather than whenever their modification times do.
+///
+/// Tools like `git checkout` update modification times even when contents stay the same,
+/// and others preserve them even when contents change.
+///
+/// What previous runs have seen is loaded from `path`, and [`save_content_hashes`] stores it
+/// back there. This way a fresh checkout needn't rebuild an output directory that is already
+/// up to date. It must be called before anything is built.
+pub(crate) fn use_content_hashes(path: PathBuf) {
+    *BUILD_CACHE.lock().unwrap() = load_build_cache(&path);
+    assert!(
+        BUILD_CACHE_PATH.set(path).is_ok(),
+        "content hashes enabled twice"
+    );
+    CONTENT_HASHES.store(true, atomic::Ordering::Relaxed);
+}
+
+/// Make [`Modified::contents`] hash the given files again even if their modification times and
+/// sizes are the same, for when they are known to have changed, such as from watch events.
+/// Relative paths are relative to the current directory. No paths means any file may have changed.
+pub(crate) fn rehash(paths: &[PathBuf]) {
+    if !CONTENT_HASHES.load(atomic::Ordering::Relaxed) {
+        return;
+    }
+    let mut cache = BUILD_CACHE.lock().unwrap();
+    let cwd = match env::current_dir() {
+        Ok(cwd) if !paths.is_empty() => cwd,
+        _ => return cache.hashed.clear(),
+    };
+    let paths = paths.iter().map(|path| cwd.join(path)).collect::<Vec<_>>();
+    cache
+        .hashed
+        .retain(|path, _| !paths.contains(&cwd.join(path)));
+}
+
+/// Store what has been seen so far for later runs to use; see [`use_content_hashes`].
+pub(crate) fn save_content_hashes() {
+    let Some(path) = BUILD_CACHE_PATH.get() else {
+        return;
+    };
+    store_build_cache(path, &BUILD_CACHE.lock().unwrap());
+}
+
+/// What has been seen by this and previous runs with [`use_content_hashes`].
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct BuildCache {
+    files: HashMap<PathBuf, SeenContents>,
+    /// The program's config; see [`Dynamic::from_fingerprint`].
+    config: Option<SeenContents>,
+    /// The modification time and size of files when their contents were last hashed,
+    /// along with the hash. This isn't kept between runs,
+    /// since some tools preserve modification times when changing files.
+    #[serde(skip)]
+    hashed: HashMap<PathBuf, ((SystemTime, u64), u64)>,
+}
+
+fn load_build_cache(path: &Path) -> BuildCache {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return BuildCache::default(),
+        Err(e) => {
+            log::warn!("failed to read build cache {}: {e}", path.display());
+            return BuildCache::default();
+        }
+    };
+    serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+        log::warn!("ignoring corrupt build cache {}: {e}", path.display());
+        BuildCache::default()
+    })
+}
+
+fn store_build_cache(path: &Path, seen: &BuildCache) {
+    let res = (|| {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_vec(seen)?)?;
+        anyhow::Ok(())
+    })();
+    // Like the disk cache, this is only an optimization.
+    if let Err(e) = res {
+        log::warn!("failed to write build cache {}: {e}", path.display());
+    }
+}
+
+/// The hash of a file's contents, or of something else, along with when it was first seen.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+struct SeenContents {
+    hash: u64,
+    modified: SystemTime,
+}
+
+impl SeenContents {
+    /// Get when the contents with the given hash were first seen, given that it is now `now`.
+    fn update(&mut self, hash: u64, now: SystemTime) -> SystemTime {
+        if hash != self.hash {
+            // The file's own modification time can't be trusted to be later than anything
+            // made from its old contents.
+            *self = Self {
+                hash,
+                modified: now,
+            };
+        }
+        self.modified
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -81,6 +363,158 @@ impl<A: Asset, F: Fn(A::Output) -> O, O> Asset for Map<A, F> {
     fn generate(&self) -> Self::Output {
         (self.f)(self.asset.generate())
     }
+    fn output_paths(&self, paths: &mut Vec<PathBuf>) {
+        self.asset.output_paths(paths);
+    }
+    fn prefetch(&self) {
+        self.asset.prefetch();
+    }
+}
+
+pub(crate) struct ParMap<A, F, O> {
+    asset: A,
+    f: Arc<F>,
+    /// The output being made on the thread pool, along with when its input was modified.
+    pending: RefCell<Option<(Modified, mpsc::Receiver<thread::Result<O>>)>>,
+}
+impl<A, F, O> ParMap<A, F, O> {
+    fn new(asset: A, f: F) -> Self {
+        Self {
+            asset,
+            f: Arc::new(f),
+            pending: RefCell::new(None),
+        }
+    }
+}
+impl<A, F, O> Asset for ParMap<A, F, O>
+where
+    A: Asset,
+    A::Output: Send + 'static,
+    F: Fn(A::Output) -> O + Send + Sync + 'static,
+    O: Send + 'static,
+{
+    type Output = O;
+
+    fn modified(&self) -> Modified {
+        self.asset.modified()
+    }
+    fn generate(&self) -> Self::Output {
+        let modified = self.asset.modified();
+        match self.pending.take() {
+            Some((input_modified, receiver)) if input_modified == modified => {
+                match receiver.recv().unwrap() {
+                    Ok(output) => output,
+                    Err(payload) => panic::resume_unwind(payload),
+                }
+            }
+            _ => (self.f)(self.asset.generate()),
+        }
+    }
+    fn output_paths(&self, paths: &mut Vec<PathBuf>) {
+        self.asset.output_paths(paths);
+    }
+    fn prefetch(&self) {
+        let Some(pool) = THREAD_POOL.get() else {
+            return;
+        };
+        let modified = self.asset.modified();
+        if matches!(*self.pending.borrow(), Some((input_modified, _)) if input_modified == modified)
+        {
+            return;
+        }
+
+        self.asset.prefetch();
+        let input = self.asset.generate();
+        let f = self.f.clone();
+        let (sender, receiver) = mpsc::channel();
+        pool.spawn(move || {
+            // Panics are caught so that they propagate from `generate` as they would otherwise.
+            let output = panic::catch_unwind(AssertUnwindSafe(|| f(input)));
+            // The receiver is gone if the input changed before the output was wanted.
+            let _ = sender.send(output);
+        });
+        *self.pending.borrow_mut() = Some((modified, receiver));
+    }
+}
+
+static THREAD_POOL: OnceCell<rayon::ThreadPool> = OnceCell::new();
+
+/// Run the functions given to [`Asset::par_map`] on `threads` threads.
+/// Without this, they run on the main thread like those given to [`Asset::map`].
+pub(crate) fn init_thread_pool(threads: usize) {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|i| format!("builder-{i}"))
+        .build()
+        .expect("failed to start thread pool");
+    assert!(
+        THREAD_POOL.set(pool).is_ok(),
+        "thread pool initialized twice"
+    );
+}
+
+pub(crate) struct AndThen<A, F> {
+    asset: A,
+    f: F,
+}
+impl<A, F> AndThen<A, F> {
+    fn new(asset: A, f: F) -> Self {
+        Self { asset, f }
+    }
+}
+impl<A, F, T, E, O> Asset for AndThen<A, F>
+where
+    A: Asset<Output = Result<T, E>>,
+    F: Fn(T) -> Result<O, E>,
+{
+    type Output = Result<O, E>;
+
+    fn modified(&self) -> Modified {
+        self.asset.modified()
+    }
+    fn generate(&self) -> Self::Output {
+        self.asset.generate().and_then(&self.f)
+    }
+    fn output_paths(&self, paths: &mut Vec<PathBuf>) {
+        self.asset.output_paths(paths);
+    }
+    fn prefetch(&self) {
+        self.asset.prefetch();
+    }
+}
+
+pub(crate) struct Zip<A, B> {
+    a: A,
+    b: B,
+}
+impl<A, B> Zip<A, B> {
+    fn new(a: A, b: B) -> Self {
+        Self { a, b }
+    }
+}
+impl<A, B, T0, E0, T1, E1> Asset for Zip<A, B>
+where
+    A: Asset<Output = Result<T0, E0>>,
+    B: Asset<Output = Result<T1, E1>>,
+    E0: Borrow<anyhow::Error>,
+    E1: Borrow<anyhow::Error>,
+{
+    type Output = Result<(T0, T1), ErrorPage>;
+
+    fn modified(&self) -> Modified {
+        Ord::max(self.a.modified(), self.b.modified())
+    }
+    fn generate(&self) -> Self::Output {
+        ErrorPage::zip(self.a.generate(), self.b.generate())
+    }
+    fn output_paths(&self, paths: &mut Vec<PathBuf>) {
+        self.a.output_paths(paths);
+        self.b.output_paths(paths);
+    }
+    fn prefetch(&self) {
+        self.a.prefetch();
+        self.b.prefetch();
+    }
 }
 
 pub(crate) struct Flatten<A> {
@@ -103,6 +537,75 @@ where
     fn generate(&self) -> Self::Output {
         self.asset.generate().generate()
     }
+    fn output_paths(&self, paths: &mut Vec<PathBuf>) {
+        self.asset.output_paths(paths);
+        // Which asset is inside isn't known until the outer one is generated, which may be
+        // expensive or have side effects, so it's left out until that has happened anyway.
+        if let Some(inner) = self.asset.peek() {
+            inner.output_paths(paths);
+        }
+    }
+    fn peek(&self) -> Option<Self::Output> {
+        self.asset.peek()?.peek()
+    }
+    fn prefetch(&self) {
+        // The inner asset isn't known until the outer one is generated.
+        self.asset.prefetch();
+    }
+}
+
+pub(crate) struct WithModified<A> {
+    asset: A,
+}
+impl<A> WithModified<A> {
+    fn new(asset: A) -> Self {
+        Self { asset }
+    }
+}
+impl<A: Asset> Asset for WithModified<A> {
+    type Output = (Modified, A::Output);
+
+    fn modified(&self) -> Modified {
+        self.asset.modified()
+    }
+    fn generate(&self) -> Self::Output {
+        (self.asset.modified(), self.asset.generate())
+    }
+    fn output_paths(&self, paths: &mut Vec<PathBuf>) {
+        self.asset.output_paths(paths);
+    }
+    fn prefetch(&self) {
+        self.asset.prefetch();
+    }
+}
+
+pub(crate) struct After<A, D> {
+    asset: A,
+    dependency: D,
+}
+impl<A, D> After<A, D> {
+    fn new(asset: A, dependency: D) -> Self {
+        Self { asset, dependency }
+    }
+}
+impl<A: Asset, D: Asset<Output = ()>> Asset for After<A, D> {
+    type Output = A::Output;
+
+    fn modified(&self) -> Modified {
+        Ord::max(self.asset.modified(), self.dependency.modified())
+    }
+    fn generate(&self) -> Self::Output {
+        self.dependency.generate();
+        self.asset.generate()
+    }
+    fn output_paths(&self, paths: &mut Vec<PathBuf>) {
+        self.dependency.output_paths(paths);
+        self.asset.output_paths(paths);
+    }
+    fn prefetch(&self) {
+        self.dependency.prefetch();
+        self.asset.prefetch();
+    }
 }
 
 pub(crate) struct Cache<A: Asset> {
@@ -136,12 +639,321 @@ where
         self.cached.set(Some((last_modified, output.clone())));
         output
     }
+    fn output_paths(&self, paths: &mut Vec<PathBuf>) {
+        self.asset.output_paths(paths);
+    }
+    fn prefetch(&self) {
+        if self.peek().is_none() {
+            self.asset.prefetch();
+        }
+    }
+    fn peek(&self) -> Option<Self::Output> {
+        let cached = self.cached.take();
+        let output = cached
+            .as_ref()
+            .filter(|&&(last_modified, _)| last_modified >= self.asset.modified())
+            .map(|(_, output)| output.clone());
+        self.cached.set(cached);
+        output
+    }
+}
+
+pub(crate) struct Dedup<A: Asset> {
+    asset: A,
+    /// When the inner asset was last generated, when its output last changed, and that output.
+    cached: RefCell<Option<(Modified, Modified, A::Output)>>,
+}
+impl<A: Asset> Dedup<A> {
+    fn new(asset: A) -> Self {
+        Self {
+            asset,
+            cached: RefCell::new(None),
+        }
+    }
+}
+impl<A: Asset> Dedup<A>
+where
+    A::Output: Clone + PartialEq,
+{
+    fn refresh(&self) {
+        let inner_modified = self.asset.modified();
+        let mut cached = self.cached.borrow_mut();
+        if cached
+            .as_ref()
+            .is_some_and(|&(generated, _, _)| generated >= inner_modified)
+        {
+            return;
+        }
+        let output = self.asset.generate();
+        let changed = match cached.take() {
+            Some((_, changed, old)) if old == output => changed,
+            _ => inner_modified,
+        };
+        *cached = Some((inner_modified, changed, output));
+    }
+}
+impl<A: Asset> Asset for Dedup<A>
+where
+    A::Output: Clone + PartialEq,
+{
+    type Output = A::Output;
+
+    fn modified(&self) -> Modified {
+        self.refresh();
+        self.cached.borrow().as_ref().unwrap().1
+    }
+    fn generate(&self) -> Self::Output {
+        self.refresh();
+        self.cached.borrow().as_ref().unwrap().2.clone()
+    }
+    fn output_paths(&self, paths: &mut Vec<PathBuf>) {
+        self.asset.output_paths(paths);
+    }
+    fn prefetch(&self) {
+        if self.peek().is_none() {
+            self.asset.prefetch();
+        }
+    }
+    fn peek(&self) -> Option<Self::Output> {
+        self.cached
+            .borrow()
+            .as_ref()
+            .filter(|&&(generated, _, _)| generated >= self.asset.modified())
+            .map(|(_, _, output)| output.clone())
+    }
+}
+
+pub(crate) struct CacheEach<A, K, V, F> {
+    asset: A,
+    capacity: usize,
+    f: F,
+    /// Each value along with the generation it was last used in.
+    entries: RefCell<HashMap<K, (u64, V)>>,
+    generation: Cell<u64>,
+}
+impl<A, K, V, F> CacheEach<A, K, V, F> {
+    fn new(asset: A, capacity: usize, f: F) -> Self {
+        Self {
+            asset,
+            capacity,
+            f,
+            entries: RefCell::new(HashMap::new()),
+            generation: Cell::new(0),
+        }
+    }
+}
+impl<A: Asset, K, V, F> Asset for CacheEach<A, K, V, F>
+where
+    A::Output: IntoIterator<Item = K>,
+    K: Hash + Eq + Clone,
+    V: Clone,
+    F: Fn(&K) -> V,
+{
+    type Output = Vec<V>;
+
+    fn modified(&self) -> Modified {
+        self.asset.modified()
+    }
+    fn generate(&self) -> Self::Output {
+        let generation = self.generation.get() + 1;
+        self.generation.set(generation);
+
+        let mut entries = self.entries.borrow_mut();
+        let values = self
+            .asset
+            .generate()
+            .into_iter()
+            .map(|key| {
+                let entry = entries
+                    .entry(key)
+                    .or_insert_with_key(|key| (generation, (self.f)(key)));
+                entry.0 = generation;
+                entry.1.clone()
+            })
+            .collect::<Vec<_>>();
+
+        // Values in use are never evicted, and don't count towards the capacity.
+        let mut unused = entries
+            .iter()
+            .filter(|(_, &(used, _))| used != generation)
+            .map(|(key, &(used, _))| (used, key.clone()))
+            .collect::<Vec<_>>();
+        let excess = unused.len().saturating_sub(self.capacity);
+        if excess > 0 {
+            unused.sort_unstable_by_key(|&(used, _)| used);
+            for (_, key) in unused.into_iter().take(excess) {
+                entries.remove(&key);
+            }
+        }
+
+        values
+    }
+    fn output_paths(&self, paths: &mut Vec<PathBuf>) {
+        self.asset.output_paths(paths);
+    }
+    fn prefetch(&self) {
+        self.asset.prefetch();
+    }
+}
+
+pub(crate) struct PersistCache<A: Asset> {
+    asset: A,
+    key: String,
+    cached: Cell<Option<(Modified, A::Output)>>,
+}
+impl<A: Asset> PersistCache<A> {
+    fn new(asset: A, key: String) -> Self {
+        Self {
+            asset,
+            key,
+            cached: Cell::new(None),
+        }
+    }
+}
+impl<A: Asset> Asset for PersistCache<A>
+where
+    A::Output: Clone + Serialize + DeserializeOwned,
+{
+    type Output = A::Output;
+
+    fn modified(&self) -> Modified {
+        self.asset.modified()
+    }
+    fn generate(&self) -> Self::Output {
+        let inner_modified = self.asset.modified();
+        let (last_modified, output) = self.load(inner_modified).unwrap_or_else(|| {
+            let output = self.asset.generate();
+            if let Some(disk_cache) = DISK_CACHE.get() {
+                disk_cache.store(&self.key, inner_modified, &output);
+            }
+            (inner_modified, output)
+        });
+        self.cached.set(Some((last_modified, output.clone())));
+        output
+    }
+    fn output_paths(&self, paths: &mut Vec<PathBuf>) {
+        self.asset.output_paths(paths);
+    }
+    fn prefetch(&self) {
+        // Anything loaded from disk is kept for `generate` so it needn't be loaded again.
+        let loaded = self.load(self.asset.modified());
+        let stale = loaded.is_none();
+        self.cached.set(loaded);
+        if stale {
+            self.asset.prefetch();
+        }
+    }
+}
+impl<A: Asset> PersistCache<A>
+where
+    A::Output: Clone + Serialize + DeserializeOwned,
+{
+    /// Take the cached output, from memory or disk, if it is still up to date.
+    fn load(&self, inner_modified: Modified) -> Option<(Modified, A::Output)> {
+        self.cached
+            .take()
+            .filter(|&(last_modified, _)| last_modified >= inner_modified)
+            .or_else(|| {
+                let disk_cache = DISK_CACHE.get()?;
+                // Without a modification time, there's no way to tell if an entry is stale.
+                if inner_modified == Modified::Never {
+                    return None;
+                }
+                disk_cache
+                    .load::<A::Output>(&self.key)
+                    .filter(|&(last_modified, _)| last_modified >= inner_modified)
+            })
+    }
+}
+
+static DISK_CACHE: OnceCell<DiskCache> = OnceCell::new();
+
+/// Store the outputs of [`Asset::persist_cache`] in `dir`.
+/// Entries are only reused by runs of the same executable whose `config` has the same hash.
+/// Dry runs read entries but don't write any.
+pub(crate) fn init_disk_cache(dir: PathBuf, config: u64, dry_run: bool) {
+    let mut hasher = DefaultHasher::new();
+    match env::current_exe().and_then(fs::read) {
+        Ok(exe) => exe.hash(&mut hasher),
+        // Without knowing which executable made them, no entries can be trusted.
+        Err(e) => {
+            log::warn!("not using disk cache: failed to read executable: {e}");
+            return;
+        }
+    }
+    config.hash(&mut hasher);
+    let disk_cache = DiskCache {
+        dir,
+        fingerprint: hasher.finish(),
+        dry_run,
+    };
+    assert!(
+        DISK_CACHE.set(disk_cache).is_ok(),
+        "disk cache already initialized"
+    );
+}
+
+struct DiskCache {
+    dir: PathBuf,
+    /// Identifies the executable and config that entries were made by.
+    fingerprint: u64,
+    dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DiskCacheEntry<T> {
+    fingerprint: u64,
+    modified: Modified,
+    output: T,
+}
+
+impl DiskCache {
+    fn path(&self, key: &str) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        self.dir.join(format!("{:016x}.json", hasher.finish()))
+    }
+
+    /// Load an entry, or return `None` if there is no usable one.
+    fn load<T: DeserializeOwned>(&self, key: &str) -> Option<(Modified, T)> {
+        let path = self.path(key);
+        let bytes = fs::read(&path).ok()?;
+        let entry = match serde_json::from_slice::<DiskCacheEntry<T>>(&bytes) {
+            Ok(entry) => entry,
+            Err(e) => {
+                log::debug!("ignoring corrupt cache entry {}: {e}", path.display());
+                return None;
+            }
+        };
+        (entry.fingerprint == self.fingerprint).then_some((entry.modified, entry.output))
+    }
+
+    fn store<T: Serialize>(&self, key: &str, modified: Modified, output: &T) {
+        if self.dry_run {
+            return;
+        }
+        let entry = DiskCacheEntry {
+            fingerprint: self.fingerprint,
+            modified,
+            output,
+        };
+        let path = self.path(key);
+        let res = (|| {
+            fs::create_dir_all(&self.dir)?;
+            fs::write(&path, serde_json::to_vec(&entry)?)?;
+            anyhow::Ok(())
+        })();
+        // The cache is only an optimization, so failing to write to it isn't an error.
+        if let Err(e) = res {
+            log::warn!("failed to write cache entry {}: {e}", path.display());
+        }
+    }
 }
 
 static EXE_MODIFIED: Lazy<Modified> = Lazy::new(|| {
     env::current_exe()
         .ok()
-        .and_then(Modified::path)
+        .and_then(Modified::contents)
         .unwrap_or_else(|| Modified::At(SystemTime::now()))
 });
 
@@ -164,11 +976,56 @@ where
         Modified::path(&self.path).unwrap_or(Modified::Never)
     }
     fn generate(&self) -> Self::Output {
-        let output_modified = self.modified();
-        if self.asset.modified() >= output_modified || *EXE_MODIFIED >= output_modified {
+        if self.stale() {
             self.asset.generate();
         }
     }
+    fn output_paths(&self, paths: &mut Vec<PathBuf>) {
+        paths.push(self.path.as_ref().to_owned());
+        self.asset.output_paths(paths);
+    }
+    fn prefetch(&self) {
+        if self.stale() {
+            self.asset.prefetch();
+        }
+    }
+}
+impl<A: Asset, P: AsRef<Path>> ModifiesPath<A, P> {
+    fn stale(&self) -> bool {
+        let output_modified = Modified::path(&self.path).unwrap_or(Modified::Never);
+        self.asset.modified() >= output_modified || *EXE_MODIFIED >= output_modified
+    }
+}
+
+pub(crate) struct CatchUnwind<A> {
+    asset: A,
+}
+impl<A> CatchUnwind<A> {
+    fn new(asset: A) -> Self {
+        Self { asset }
+    }
+}
+impl<A: Asset<Output = ()>> Asset for CatchUnwind<A> {
+    type Output = ();
+
+    fn modified(&self) -> Modified {
+        self.asset.modified()
+    }
+    fn generate(&self) -> Self::Output {
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| self.asset.generate())) {
+            let message = payload
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown panic payload");
+            log::error!("panicked while generating asset: {message}");
+        }
+    }
+    fn output_paths(&self, paths: &mut Vec<PathBuf>) {
+        self.asset.output_paths(paths);
+    }
+    // Prefetching isn't forwarded, since panics while doing it wouldn't be caught.
+    // It happens anyway within `generate` when the inner asset contains an `all`.
 }
 
 macro_rules! impl_for_refs {
@@ -182,6 +1039,15 @@ macro_rules! impl_for_refs {
             fn generate(&self) -> Self::Output {
                 (**self).generate()
             }
+            fn output_paths(&self, paths: &mut Vec<PathBuf>) {
+                (**self).output_paths(paths);
+            }
+            fn prefetch(&self) {
+                (**self).prefetch();
+            }
+            fn peek(&self) -> Option<Self::Output> {
+                (**self).peek()
+            }
         }
     )* };
 }
@@ -218,9 +1084,18 @@ macro_rules! impl_for_tuples {
                 }
                 #[allow(clippy::unused_unit)]
                 fn generate(&self) -> Self::Output {
+                    self.prefetch();
                     let Self($($ident,)*) = self;
                     ($($ident.generate(),)*)
                 }
+                fn output_paths(&self, paths: &mut Vec<PathBuf>) {
+                    let Self($($ident,)*) = self;
+                    $($ident.output_paths(paths);)*
+                }
+                fn prefetch(&self) {
+                    let Self($($ident,)*) = self;
+                    $($ident.prefetch();)*
+                }
             }
 
             impl<$($ident: Asset,)*> IntoAll for ($($ident,)*) {
@@ -234,7 +1109,7 @@ macro_rules! impl_for_tuples {
         impl_for_tuples!(@$($ident)*);
     };
 }
-impl_for_tuples!(A B C D E F G H I);
+impl_for_tuples!(A B C D E F G H I J K L);
 
 macro_rules! impl_for_seq {
     ($($ty:ty),*) => { $(
@@ -249,8 +1124,17 @@ macro_rules! impl_for_seq {
                     self.0.iter().map(A::modified).max().unwrap_or(Modified::Never)
                 }
                 fn generate(&self) -> Self::Output {
+                    self.prefetch();
                     self.0.iter().map(A::generate).collect()
                 }
+                fn output_paths(&self, paths: &mut Vec<PathBuf>) {
+                    for asset in &*self.0 {
+                        asset.output_paths(paths);
+                    }
+                }
+                fn prefetch(&self) {
+                    self.0.iter().for_each(A::prefetch);
+                }
             }
 
             impl<A: Asset> IntoAll for $ty {
@@ -264,6 +1148,7 @@ macro_rules! impl_for_seq {
 }
 impl_for_seq!(Box<[A]>, std::rc::Rc<[A]>, Vec<A>);
 
+#[derive(Clone)]
 pub(crate) struct Constant<T> {
     value: T,
 }
@@ -286,21 +1171,39 @@ impl<T: Clone> Asset for Constant<T> {
 #[derive(Clone, Copy)]
 pub(crate) struct Dynamic<T> {
     value: T,
-    created: SystemTime,
+    modified: Modified,
 }
 impl<T> Dynamic<T> {
     pub(crate) fn new(value: T) -> Self {
-        Self {
-            value,
-            created: SystemTime::now(),
+        Self::modified_at(value, Modified::At(SystemTime::now()))
+    }
+
+    /// A value that was last modified at the given time, such as that of what it was made from.
+    pub(crate) fn modified_at(value: T, modified: Modified) -> Self {
+        Self { value, modified }
+    }
+
+    /// Like [`Self::new`], but with [`use_content_hashes`] it is modified when the program was
+    /// first run with a config of the given fingerprint, so that it stays the same between runs
+    /// unless the config changes. Arguments that don't affect the output shouldn't be part of it.
+    pub(crate) fn from_fingerprint(value: T, fingerprint: u64) -> Self {
+        if !CONTENT_HASHES.load(atomic::Ordering::Relaxed) {
+            return Self::new(value);
         }
+        let now = SystemTime::now();
+        let mut cache = BUILD_CACHE.lock().unwrap();
+        let seen = cache.config.get_or_insert(SeenContents {
+            hash: fingerprint,
+            modified: now,
+        });
+        Self::modified_at(value, Modified::At(seen.update(fingerprint, now)))
     }
 }
 impl<T: Clone> Asset for Dynamic<T> {
     type Output = T;
 
     fn modified(&self) -> Modified {
-        Modified::At(self.created)
+        self.modified
     }
     fn generate(&self) -> Self::Output {
         self.value.clone()
@@ -331,7 +1234,7 @@ impl<P: AsRef<Path>> Asset for FsPath<P> {
     type Output = ();
 
     fn modified(&self) -> Modified {
-        Modified::path(&self.path).unwrap_or(Modified::Never)
+        Modified::contents(&self.path).unwrap_or(Modified::Never)
     }
     fn generate(&self) -> Self::Output {}
 }
@@ -351,7 +1254,7 @@ impl<P: AsRef<Path>> Asset for TextFile<P> {
     type Output = anyhow::Result<String>;
 
     fn modified(&self) -> Modified {
-        Modified::path(&self.path).unwrap_or(Modified::Never)
+        Modified::contents(&self.path).unwrap_or(Modified::Never)
     }
     fn generate(&self) -> Self::Output {
         let path = self.path.as_ref();
@@ -405,11 +1308,230 @@ impl Iterator for DirFiles {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn cache_each() {
+        struct Keys(RefCell<Vec<&'static str>>);
+        impl Asset for Keys {
+            type Output = Vec<&'static str>;
+            fn modified(&self) -> Modified {
+                Modified::Never
+            }
+            fn generate(&self) -> Self::Output {
+                self.0.borrow().clone()
+            }
+        }
+
+        let keys = Keys(RefCell::new(vec!["a", "b"]));
+        let made = Cell::new(0);
+        let asset = (&keys).cache_each(1, |&key| {
+            made.set(made.get() + 1);
+            format!("{key}{}", made.get())
+        });
+
+        assert_eq!(asset.generate(), ["a1", "b2"]);
+        *keys.0.borrow_mut() = vec!["b"];
+        assert_eq!(asset.generate(), ["b2"]);
+
+        // Only one unused value is kept, and `a` is the least recently used.
+        *keys.0.borrow_mut() = vec!["c", "d"];
+        assert_eq!(asset.generate(), ["c3", "d4"]);
+        *keys.0.borrow_mut() = vec!["a", "b", "c", "d"];
+        assert_eq!(asset.generate(), ["a5", "b2", "c3", "d4"]);
+    }
+
+    #[test]
+    fn dedup() {
+        struct Value(Cell<(Modified, u32)>);
+        impl Asset for Value {
+            type Output = u32;
+            fn modified(&self) -> Modified {
+                self.0.get().0
+            }
+            fn generate(&self) -> Self::Output {
+                self.0.get().1
+            }
+        }
+
+        let at = |secs| Modified::At(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+        let value = Value(Cell::new((at(1), 5)));
+        let asset = (&value).map(|n| n / 2).dedup();
+        assert_eq!((asset.modified(), asset.generate()), (at(1), 2));
+
+        // The output comes out the same, so it isn't considered modified.
+        value.0.set((at(2), 4));
+        assert_eq!((asset.modified(), asset.generate()), (at(1), 2));
+
+        value.0.set((at(3), 6));
+        assert_eq!((asset.modified(), asset.generate()), (at(3), 3));
+    }
+
+    #[test]
+    fn zip() {
+        let at = |secs| Modified::At(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+        let ok = Dynamic::modified_at(Ok::<_, Rc<anyhow::Error>>(1), at(2));
+        let err = || Dynamic::modified_at(Rc::new(anyhow!("failed")), at(1)).map(Err::<(), _>);
+
+        let zipped = (&ok).zip(Constant::new(Ok::<_, Rc<anyhow::Error>>("a")));
+        assert_eq!(zipped.modified(), at(2));
+        assert_eq!(zipped.generate().ok(), Some((1, "a")));
+
+        let zipped = (&ok).zip(err());
+        assert_eq!(zipped.modified(), at(2));
+        assert_eq!(
+            zipped
+                .generate()
+                .unwrap_err()
+                .into_html()
+                .matches("failed")
+                .count(),
+            1
+        );
+
+        let zipped = err().zip(err());
+        assert_eq!(
+            zipped
+                .generate()
+                .unwrap_err()
+                .into_html()
+                .matches("failed")
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn par_map() {
+        super::init_thread_pool(2);
+
+        let input = Constant::new(1);
+        let asset = all((
+            (&input).par_map(|n| (n + 1, thread::current().id())),
+            (&input).par_map(|n| (n + 2, thread::current().id())),
+        ));
+        let ((a, a_thread), (b, b_thread)) = asset.generate();
+        assert_eq!((a, b), (2, 3));
+        assert_ne!(a_thread, thread::current().id());
+        assert_ne!(b_thread, thread::current().id());
+
+        // Without being prefetched, it runs on the current thread.
+        let (_, thread) = (&input).par_map(|n| (n, thread::current().id())).generate();
+        assert_eq!(thread, thread::current().id());
+    }
+
+    #[test]
+    fn seen_contents() {
+        let time = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        let mut seen = SeenContents {
+            hash: 1,
+            modified: time(1),
+        };
+        assert_eq!(seen.update(1, time(2)), time(1));
+        assert_eq!(seen.update(2, time(3)), time(3));
+        assert_eq!(seen.update(2, time(4)), time(3));
+        assert_eq!(seen.update(1, time(5)), time(5));
+    }
+
+    #[test]
+    fn build_cache() {
+        let path = env::temp_dir().join(format!("builder-build-cache-{}", process::id()));
+        assert_eq!(super::load_build_cache(&path), BuildCache::default());
+
+        let seen = SeenContents {
+            hash: 1,
+            modified: SystemTime::UNIX_EPOCH,
+        };
+        let cache = BuildCache {
+            files: HashMap::from([(PathBuf::from("src/blog/post.md"), seen)]),
+            config: Some(seen),
+            hashed: HashMap::new(),
+        };
+        super::store_build_cache(&path, &cache);
+        assert_eq!(super::load_build_cache(&path), cache);
+
+        fs::write(&path, "corrupt").unwrap();
+        assert_eq!(super::load_build_cache(&path), BuildCache::default());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn disk_cache() {
+        let dir = env::temp_dir().join(format!("builder-disk-cache-{}", process::id()));
+        let cache = DiskCache {
+            dir: dir.clone(),
+            fingerprint: 1,
+            dry_run: false,
+        };
+        let modified = Modified::At(SystemTime::UNIX_EPOCH);
+
+        assert_eq!(cache.load::<String>("a"), None);
+        cache.store("a", modified, &"output");
+        assert_eq!(cache.load("a"), Some((modified, "output".to_owned())));
+        assert_eq!(cache.load::<String>("b"), None);
+
+        // Entries made by other builds aren't used.
+        let other = DiskCache {
+            dir: dir.clone(),
+            fingerprint: 2,
+            dry_run: false,
+        };
+        assert_eq!(other.load::<String>("a"), None);
+
+        fs::write(cache.path("a"), "corrupt").unwrap();
+        assert_eq!(cache.load::<String>("a"), None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    use super::all;
+    use super::Asset;
+    use super::BuildCache;
+    use super::Constant;
+    use super::DiskCache;
+    use super::Dynamic;
+    use super::Modified;
+    use super::SeenContents;
+    use anyhow::anyhow;
+    use std::cell::Cell;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+    use std::rc::Rc;
+    use std::thread;
+    use std::time::Duration;
+    use std::time::SystemTime;
+}
+
+use crate::util::ErrorPage;
 use anyhow::Context as _;
 use once_cell::sync::Lazy;
+use once_cell::sync::OnceCell;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+use std::borrow::Borrow;
 use std::cell::Cell;
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
 use std::env;
 use std::fs;
+use std::hash::Hash;
+use std::hash::Hasher as _;
+use std::io;
+use std::panic;
+use std::panic::AssertUnwindSafe;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::atomic;
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
 use std::time::SystemTime;
@@ -1,22 +1,372 @@
-#[derive(Serialize)] // Serialization used in templates
+// Serialization used in templates and to cache rendered Markdown
+#[derive(Clone, Serialize, Deserialize)]
 #[cfg_attr(test, derive(Debug, PartialEq))]
 pub(crate) struct Markdown {
     pub(crate) title: String,
+    /// The title as plain text, for where HTML can't be used.
+    pub(crate) title_text: String,
     pub(crate) body: String,
     pub(crate) summary: String,
     pub(crate) outline: String,
+    /// Problems found in the source. They are also shown inline in the body.
+    #[serde(skip)]
+    pub(crate) diagnostics: Vec<Diagnostic>,
 }
 
-pub(crate) fn parse(source: &str) -> Markdown {
-    let options = pulldown_cmark::Options::empty()
-        | pulldown_cmark::Options::ENABLE_TABLES
-        | pulldown_cmark::Options::ENABLE_HEADING_ATTRIBUTES
-        | pulldown_cmark::Options::ENABLE_STRIKETHROUGH
-        | pulldown_cmark::Options::ENABLE_SMART_PUNCTUATION;
+impl Markdown {
+    /// Log the diagnostics of Markdown read from the given file, if there are any.
+    /// `line_offset` is the number of lines in the file before the Markdown, such as front matter.
+    pub(crate) fn log_diagnostics(&self, path: &Path, line_offset: usize) {
+        if self.diagnostics.is_empty() {
+            return;
+        }
+        let count = self.diagnostics.len();
+        let s = if count == 1 { "" } else { "s" };
+        log::warn!("{count} issue{s} in {}", path.display());
+        for Diagnostic {
+            message,
+            line,
+            column,
+            ..
+        } in &self.diagnostics
+        {
+            let line = line + line_offset;
+            log::warn!("{}:{line}:{column}: {message}", path.display());
+        }
+    }
+}
+
+/// A problem found while rendering Markdown.
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub(crate) struct Diagnostic {
+    pub(crate) kind: DiagnosticKind,
+    pub(crate) message: String,
+    /// The 1-based line of the source the problem occurred on.
+    pub(crate) line: usize,
+    /// The 1-based column, in characters, of the start of the construct with the problem.
+    pub(crate) column: usize,
+}
+
+/// The sort of problem a [`Diagnostic`] is about.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum DiagnosticKind {
+    /// A list type hint that is invalid or not followed by an ordered list.
+    ListTypeHint,
+    /// An ID or class where they aren't allowed.
+    DisallowedAttribute,
+    /// A heading without an ID, or one that skips a level.
+    Heading,
+    /// A code block with an unknown flag or language.
+    CodeBlock,
+    /// An undefined link reference or an invalid image attribute.
+    Link,
+    /// A footnote that is undefined, defined twice, never referenced or referenced from the title.
+    Footnote,
+}
+
+/// Options controlling how Markdown is rendered.
+#[derive(Clone, Default, Hash)]
+pub(crate) struct Options {
+    /// How to link headings to themselves.
+    pub anchor_style: AnchorStyle,
+    /// The text inside heading anchor links, when they don't wrap the heading's text.
+    /// Empty by default, leaving it to CSS to display a symbol.
+    pub anchor_text: String,
+    /// Whether to support hints of the form `<!-- type=a -->` directly before an ordered list,
+    /// which set the list's `type` attribute.
+    /// Markdown itself has no way to express lists numbered with letters or Roman numerals.
+    pub list_type_hints: bool,
+    /// Whether to give every paragraph an ID, so readers can link to it.
+    /// IDs are derived from a hash of the paragraph's content,
+    /// so they stay the same as long as the paragraph does.
+    pub paragraph_ids: bool,
+    /// Whether to load the first image eagerly with high priority, since it is likely the
+    /// largest content on the page, and lazy-load the rest.
+    pub image_loading_hints: bool,
+    /// Responsive versions of images, which turn them into `<picture>`s.
+    pub image_variants: ImageVariants,
+    /// HTML appended inside links to other sites, such as an icon. Empty to disable.
+    /// Absolute links are assumed to be external unless they are to [`Self::origin`].
+    pub external_link_icon: String,
+    /// Which Markdown extensions are enabled.
+    pub features: Features,
+    /// Written before the IDs of footnotes and their references, such as `review-1-`,
+    /// so that several documents rendered onto one page don't share any. Empty by default.
+    pub footnote_prefix: String,
+    /// The path the site is hosted under, ending in `/`.
+    /// Root-relative links and images are rebased onto it, so at the root it is just `/`.
+    /// Empty leaves them untouched, which is the default.
+    pub base_path: String,
+    /// The origin the site is hosted at, like `https://example.com`, without a trailing slash.
+    /// Empty if it isn't known.
+    pub origin: String,
+}
+
+/// Resized or re-encoded copies of images, keyed by the URL images are written with.
+/// The variants of an image are offered to the browser in a `<picture>`
+/// with one `<source>` per image type, and the original as the fallback.
+pub(crate) type ImageVariants = BTreeMap<String, Vec<ImageVariant>>;
+
+#[derive(Clone, Hash, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ImageVariant {
+    /// The URL of the variant, resolved the same way as the original.
+    pub src: String,
+    /// The width of the variant in pixels.
+    pub width: u32,
+}
+
+/// Read a JSON manifest of [`ImageVariants`], such as one written by an image optimizer.
+pub(crate) fn read_image_variants(path: &Path) -> anyhow::Result<ImageVariants> {
+    let src =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_str(&src).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+/// Extensions to standard Markdown that may be individually disabled,
+/// for example to render less-trusted input.
+#[derive(Clone, Hash, clap::Args)]
+pub(crate) struct Features {
+    /// Disable Markdown tables.
+    #[clap(long = "no-markdown-tables", action = clap::ArgAction::SetFalse)]
+    pub tables: bool,
+    /// Escape raw HTML in Markdown instead of passing it through.
+    #[clap(long = "no-markdown-raw-html", action = clap::ArgAction::SetFalse)]
+    pub raw_html: bool,
+    /// Disable `{#id .class}` attributes on Markdown headings.
+    #[clap(long = "no-markdown-heading-attributes", action = clap::ArgAction::SetFalse)]
+    pub heading_attributes: bool,
+    /// Disable `~~strikethrough~~` in Markdown.
+    #[clap(long = "no-markdown-strikethrough", action = clap::ArgAction::SetFalse)]
+    pub strikethrough: bool,
+    /// Disable conversion of quotes, dashes and ellipses to their typographic forms.
+    #[clap(long = "no-markdown-smart-punctuation", action = clap::ArgAction::SetFalse)]
+    pub smart_punctuation: bool,
+    /// Disable `[^label]` footnotes in Markdown.
+    #[clap(long = "no-markdown-footnotes", action = clap::ArgAction::SetFalse)]
+    pub footnotes: bool,
+    /// Disable `- [ ]` task list checkboxes in Markdown.
+    #[clap(long = "no-markdown-task-lists", action = clap::ArgAction::SetFalse)]
+    pub task_lists: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Self {
+            tables: true,
+            raw_html: true,
+            heading_attributes: true,
+            strikethrough: true,
+            smart_punctuation: true,
+            footnotes: true,
+            task_lists: true,
+        }
+    }
+}
+
+impl Features {
+    fn parser_options(&self) -> pulldown_cmark::Options {
+        let mut options = pulldown_cmark::Options::empty();
+        options.set(pulldown_cmark::Options::ENABLE_TABLES, self.tables);
+        options.set(
+            pulldown_cmark::Options::ENABLE_HEADING_ATTRIBUTES,
+            self.heading_attributes,
+        );
+        options.set(
+            pulldown_cmark::Options::ENABLE_STRIKETHROUGH,
+            self.strikethrough,
+        );
+        options.set(
+            pulldown_cmark::Options::ENABLE_SMART_PUNCTUATION,
+            self.smart_punctuation,
+        );
+        options.set(pulldown_cmark::Options::ENABLE_FOOTNOTES, self.footnotes);
+        options.set(pulldown_cmark::Options::ENABLE_TASKLISTS, self.task_lists);
+        options
+    }
+
+    /// Apply a document's overrides on top of these features.
+    pub(crate) fn with_overrides(&self, overrides: FeatureOverrides) -> Self {
+        let FeatureOverrides {
+            tables,
+            raw_html,
+            heading_attributes,
+            strikethrough,
+            smart_punctuation,
+            footnotes,
+            task_lists,
+        } = overrides;
+        Self {
+            tables: tables.unwrap_or(self.tables),
+            raw_html: raw_html.unwrap_or(self.raw_html),
+            heading_attributes: heading_attributes.unwrap_or(self.heading_attributes),
+            strikethrough: strikethrough.unwrap_or(self.strikethrough),
+            smart_punctuation: smart_punctuation.unwrap_or(self.smart_punctuation),
+            footnotes: footnotes.unwrap_or(self.footnotes),
+            task_lists: task_lists.unwrap_or(self.task_lists),
+        }
+    }
+}
+
+/// Overrides of the site-wide [`Features`] for a single document, such as from a post's
+/// front matter. Features that aren't mentioned keep their site-wide setting.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct FeatureOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tables: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_html: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heading_attributes: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strikethrough: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smart_punctuation: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub footnotes: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task_lists: Option<bool>,
+}
+
+#[derive(Clone, Copy, Default, Hash, clap::ValueEnum)]
+pub(crate) enum AnchorStyle {
+    /// An anchor link before the heading text.
+    #[default]
+    Leading,
+    /// An anchor link after the heading text.
+    Trailing,
+    /// No anchor link at all.
+    None,
+    /// The heading text itself is the anchor link.
+    /// Links within headings will result in invalid nested `<a>`s.
+    WrapText,
+}
+
+/// Render a Markdown fragment that is not a whole page, such as a comment, to HTML
+/// with the default options. See [`RenderHtml`] to configure it.
+// This and `RenderHtml` are for embedding the renderer elsewhere, so the site doesn't use them.
+#[allow(dead_code)]
+pub(crate) fn render_html(source: &str) -> anyhow::Result<String> {
+    RenderHtml::new().render(source)
+}
+
+/// A configurable way to render a standalone Markdown string to HTML,
+/// for when the rest of a [`Markdown`] isn't needed.
+///
+/// Unlike with [`parse`], a top-level heading is kept in the output,
+/// and other headings need not have IDs unless the outline is included.
+#[allow(dead_code)]
+#[derive(Clone, Default)]
+pub(crate) struct RenderHtml {
+    options: Options,
+    summary: bool,
+    outline: bool,
+}
+
+#[allow(dead_code)]
+impl RenderHtml {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the options to render with, replacing any set so far.
+    pub(crate) fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Whether to convert quotes, dashes and ellipses to their typographic forms.
+    /// Enabled by default.
+    pub(crate) fn smart_punctuation(mut self, smart_punctuation: bool) -> Self {
+        self.options.features.smart_punctuation = smart_punctuation;
+        self
+    }
+
+    /// Whether to render only the summary, the text of the first paragraph,
+    /// instead of the whole body. Disabled by default.
+    pub(crate) fn summary(mut self, summary: bool) -> Self {
+        self.summary = summary;
+        self
+    }
+
+    /// Whether to put the outline of the headings in a `<nav>` before the body.
+    /// Disabled by default.
+    pub(crate) fn outline(mut self, outline: bool) -> Self {
+        self.outline = outline;
+        self
+    }
+
+    /// Render the source, failing if there were any problems with it.
+    pub(crate) fn render(&self, source: &str) -> anyhow::Result<String> {
+        let markdown = parse_with(source, &self.options, !self.outline);
+        if let Some(Diagnostic {
+            message,
+            line,
+            column,
+            ..
+        }) = markdown.diagnostics.first()
+        {
+            let more = markdown.diagnostics.len() - 1;
+            let more = match more {
+                0 => String::new(),
+                1 => " (and 1 more problem)".to_owned(),
+                _ => format!(" (and {more} more problems)"),
+            };
+            anyhow::bail!("{line}:{column}: {message}{more}");
+        }
+
+        if self.summary {
+            return Ok(markdown.summary);
+        }
+        let mut html = String::new();
+        if self.outline && !markdown.outline.is_empty() {
+            html.push_str("<nav>");
+            html.push_str(&markdown.outline);
+            html.push_str("</nav>");
+        }
+        if !markdown.title.is_empty() {
+            html.push_str("<h1>");
+            html.push_str(&markdown.title);
+            html.push_str("</h1>");
+        }
+        html.push_str(&markdown.body);
+        Ok(html)
+    }
+}
+
+pub(crate) fn parse(source: &str, options: &Options) -> Markdown {
+    parse_with(source, options, false)
+}
+
+fn parse_with(source: &str, options: &Options, fragment: bool) -> Markdown {
+    let parser_options = options.features.parser_options();
+
+    // Resolve undefined references to links whose destination is the reference label,
+    // so that they can be reported where they occur.
+    // Shortcut references are left alone, since `[text]` is commonly meant literally.
+    let mut broken_link_callback = |link: pulldown_cmark::BrokenLink<'_>| {
+        let label = pulldown_cmark::CowStr::from(link.reference.into_string());
+        (link.link_type != pulldown_cmark::LinkType::Shortcut).then(|| (label, "".into()))
+    };
 
     Renderer {
-        parser: pulldown_cmark::Parser::new_ext(source, options),
+        parser: pulldown_cmark::Parser::new_with_broken_link_callback(
+            source,
+            parser_options,
+            Some(&mut broken_link_callback),
+        )
+        .into_offset_iter(),
+        source,
+        offset: 0,
+        diagnostics: Vec::new(),
+        options,
+        fragment,
         title: String::new(),
+        title_text: String::new(),
         in_title: false,
         body: String::new(),
         summary: String::new(),
@@ -26,6 +376,15 @@ pub(crate) fn parse(source: &str) -> Markdown {
         outline: String::new(),
         outline_level: 1,
         in_heading: false,
+        list_type: None,
+        paragraph_start: None,
+        paragraph_ids: HashSet::new(),
+        lists: Vec::new(),
+        images: 0,
+        external_link: false,
+        footnote_numbers: HashMap::new(),
+        footnotes: Vec::new(),
+        outside_footnote: None,
         syntax_set: &SYNTAX_SET,
     }
     .render()
@@ -35,11 +394,115 @@ pub(crate) fn theme_css(theme: &Theme) -> String {
     syntect::html::css_for_theme_with_class_style(theme, SYNTECT_CLASS_STYLE).unwrap()
 }
 
+/// CSS for highlighted code, using `dark.tmTheme` and `light.tmTheme` in the given directory
+/// according to the user's preferred color scheme.
+///
+/// Any other `NAME.tmTheme` in the directory applies to code inside an element
+/// with the class `code-theme-NAME`.
+pub(crate) fn themes_css_asset(dir: &Path) -> impl Asset<Output = Rc<String>> {
+    let dark = theme_asset(dir.join("dark.tmTheme"), Some("base16-ocean.dark"));
+    let light = theme_asset(dir.join("light.tmTheme"), Some("base16-ocean.light"));
+
+    let named = asset::Dir::new(dir.to_owned())
+        .map(|files| -> anyhow::Result<_> {
+            let mut themes = Vec::new();
+            for path in files? {
+                let path = path?;
+                if path.extension() != Some("tmTheme".as_ref()) {
+                    continue;
+                }
+                let name = match path.file_stem().unwrap().to_str() {
+                    Some("dark" | "light") => continue,
+                    Some(name) => name.to_owned(),
+                    None => {
+                        log::error!("filename `{}` is not valid UTF-8", path.display());
+                        continue;
+                    }
+                };
+                let theme = theme_asset(path, None)
+                    .map(move |css| scope_css(&css, &format!(".code-theme-{name}")));
+                themes.push(theme);
+            }
+            Ok(asset::all(themes).map(|themes| themes.concat()))
+        })
+        .map(|res| -> Rc<dyn Asset<Output = _>> {
+            match res {
+                Ok(asset) => Rc::new(asset),
+                Err(e) => {
+                    log::error!("{e:?}");
+                    Rc::new(asset::Constant::new(String::new()))
+                }
+            }
+        })
+        .cache()
+        .flatten();
+
+    asset::all((dark, light, named))
+        .map(|(dark, light, named)| {
+            Rc::new(format!(
+                "{dark}@media(prefers-color-scheme:light){{{light}}}{named}"
+            ))
+        })
+        .cache()
+}
+
+/// Load the theme at the given path, falling back to the named built-in theme
+/// or no styles at all.
+fn theme_asset(path: PathBuf, fallback: Option<&'static str>) -> impl Asset<Output = Rc<String>> {
+    asset::FsPath::new(path.clone())
+        .map(move |()| {
+            let res = ThemeSet::get_theme(&path)
+                .with_context(|| format!("failed to read theme file {}", path.display()));
+            Rc::new(match (res, fallback) {
+                (Ok(theme), _) => theme_css(&theme),
+                (Err(e), Some(fallback)) => {
+                    log::warn!("{e:?}\nfalling back to the built-in {fallback}");
+                    theme_css(&ThemeSet::load_defaults().themes[fallback])
+                }
+                (Err(e), None) => {
+                    log::error!("{e:?}");
+                    String::new()
+                }
+            })
+        })
+        .cache()
+}
+
+/// Restrict the rules of generated theme CSS to descendants of the given selector.
+fn scope_css(css: &str, scope: &str) -> String {
+    let mut scoped = String::with_capacity(css.len());
+    for line in css.lines() {
+        match line.strip_suffix('{') {
+            Some(selectors) => {
+                for (i, selector) in selectors.trim_end().split(", ").enumerate() {
+                    if i != 0 {
+                        scoped.push_str(", ");
+                    }
+                    push!(scoped, "{scope} {selector}");
+                }
+                scoped.push_str(" {");
+            }
+            None => scoped.push_str(line),
+        }
+        scoped.push('\n');
+    }
+    scoped
+}
+
 struct Renderer<'a> {
-    parser: pulldown_cmark::Parser<'a, 'a>,
+    parser: pulldown_cmark::OffsetIter<'a, 'a>,
+    source: &'a str,
+    /// The position in the source of the start of the last event.
+    offset: usize,
+    diagnostics: Vec<Diagnostic>,
+    options: &'a Options,
+    /// Whether we are rendering a fragment rather than a whole page.
+    fragment: bool,
     title: String,
     /// Whether we are currently writing to the title instead of the body.
     in_title: bool,
+    /// The title as plain text.
+    title_text: String,
     body: String,
     summary: String,
     /// Whether we are currently writing to the summary.
@@ -56,56 +519,114 @@ struct Renderer<'a> {
     /// Whether we are in a `<hN>` tag.
     /// Used to determine whether to also write to the outline.
     in_heading: bool,
+    /// The `type` of the next ordered list, given by a list type hint.
+    list_type: Option<&'static str>,
+    /// The position in the body just after the `<p` of the current paragraph,
+    /// if it is to be given an ID.
+    paragraph_start: Option<usize>,
+    /// The paragraph IDs used so far, to keep them unique.
+    paragraph_ids: HashSet<String>,
+    /// For each list we are in, the position in the body just after its `<ol` or `<ul`
+    /// and whether it has task list checkboxes, in which case it's given a class.
+    lists: Vec<(usize, bool)>,
+    /// The number of images encountered so far.
+    images: usize,
+    /// Whether the current link should have the external link icon appended.
+    external_link: bool,
+    /// The number of each footnote, in the order they were first referenced,
+    /// and the position in the source of that first reference.
+    footnote_numbers: HashMap<String, (usize, usize)>,
+    /// The footnote definitions seen so far, to be written at the end of the body.
+    footnotes: Vec<Footnote>,
+    /// While rendering a footnote definition, which goes to its own buffer,
+    /// the definition's label and the body outside it.
+    outside_footnote: Option<(String, String)>,
     syntax_set: &'a SyntaxSet,
 }
 
 impl<'a> Renderer<'a> {
     fn render(mut self) -> Markdown {
-        while let Some(event) = self.parser.next() {
+        while let Some(event) = self.next_event() {
+            if self.list_type.is_some()
+                && !matches!(
+                    event,
+                    pulldown_cmark::Event::Start(pulldown_cmark::Tag::List(Some(_)))
+                )
+            {
+                self.list_type = None;
+                self.error(
+                    DiagnosticKind::ListTypeHint,
+                    "list type hint is not directly followed by an ordered list",
+                );
+            }
+
             match event {
                 pulldown_cmark::Event::Start(tag) => self.start_tag(tag),
                 pulldown_cmark::Event::End(tag) => self.end_tag(tag),
                 pulldown_cmark::Event::Text(text) => {
-                    self.push_summary(&text);
+                    self.push_text(&text);
                     escape_html(&mut self, &text);
                 }
                 pulldown_cmark::Event::Code(text) => {
                     self.push_str("<code class='scode'>");
 
-                    let (language, code) =
-                        match text.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
-                            Some((language, code)) => (Some(language), code),
-                            None => (None, &*text),
-                        };
+                    let (language, code) = inline_code_language(&text);
 
                     if let Some(language) = language {
                         self.syntax_highlight(language, code);
                     } else {
-                        escape_html(&mut self, &text);
+                        escape_html(&mut self, code);
                     }
 
-                    self.push_summary(code);
+                    self.push_text(code);
 
                     self.push_str("</code>");
                 }
-                pulldown_cmark::Event::Html(html) => self.push_str(&html),
+                pulldown_cmark::Event::Html(html) => {
+                    match list_type_hint(&html).filter(|_| self.options.list_type_hints) {
+                        Some(hint) => match LIST_TYPES.into_iter().find(|&ty| ty == hint) {
+                            Some(list_type) => self.list_type = Some(list_type),
+                            None => self.error(
+                                DiagnosticKind::ListTypeHint,
+                                format_args!("invalid list type {hint}"),
+                            ),
+                        },
+                        None if self.options.features.raw_html => self.push_str(&html),
+                        None => escape_html(&mut self, &html),
+                    }
+                }
                 pulldown_cmark::Event::SoftBreak => {
-                    self.push_summary(" ");
+                    self.push_text(" ");
                     self.push_str(" ");
                 }
                 pulldown_cmark::Event::HardBreak => {
-                    self.push_summary(" ");
+                    self.push_text(" ");
                     self.push_str("<br>");
                 }
                 pulldown_cmark::Event::Rule => self.push_str("<hr>"),
-                // We do not enable these extensions
-                pulldown_cmark::Event::FootnoteReference(_)
-                | pulldown_cmark::Event::TaskListMarker(_) => unreachable!(),
+                pulldown_cmark::Event::FootnoteReference(label) => {
+                    self.footnote_reference(&label);
+                }
+                // Not added to the summary, since it isn't text.
+                pulldown_cmark::Event::TaskListMarker(checked) => {
+                    if let Some((_, task_list)) = self.lists.last_mut() {
+                        *task_list = true;
+                    }
+                    self.push_str("<input type='checkbox' disabled");
+                    if checked {
+                        self.push_str(" checked");
+                    }
+                    self.push_str(">");
+                }
             }
         }
 
         assert!(!self.in_table_head);
         assert!(!self.in_heading);
+        assert!(self.outside_footnote.is_none());
+        assert!(self.lists.is_empty());
+
+        self.push_footnotes();
 
         // Close remaining opened tags in the outline.
         for _ in 0..self.outline_level - 1 {
@@ -113,38 +634,88 @@ impl<'a> Renderer<'a> {
         }
 
         if !self.used_classes.is_empty() {
+            // Sort the definitions so that the output doesn't depend on hash set order.
+            let mut definitions: Vec<_> = self
+                .used_classes
+                .iter()
+                .map(|class| {
+                    let mut definition = String::new();
+                    class.write_definition(&mut definition);
+                    definition
+                })
+                .collect();
+            definitions.sort_unstable();
+
             self.push_str("<style>");
-            for class in &self.used_classes {
-                class.write_definition(&mut self.body);
+            for definition in definitions {
+                self.push_str(&definition);
             }
             self.push_str("</style>");
         }
 
         Markdown {
             title: self.title,
+            title_text: self.title_text,
             body: self.body,
             summary: self.summary,
             outline: self.outline,
+            diagnostics: self.diagnostics,
         }
     }
 
+    fn next_event(&mut self) -> Option<pulldown_cmark::Event<'a>> {
+        let (event, range) = self.parser.next()?;
+        self.offset = range.start;
+        Some(event)
+    }
+
     fn start_tag(&mut self, tag: pulldown_cmark::Tag<'a>) {
         match tag {
             pulldown_cmark::Tag::Paragraph => {
-                if self.summary.is_empty() {
+                if self.summary.is_empty() && self.outside_footnote.is_none() {
                     self.in_summary = true;
                 }
+                if self.options.paragraph_ids {
+                    self.paragraph_start = Some(self.body.len() + "<p".len());
+                }
                 self.push_str("<p>");
             }
-            pulldown_cmark::Tag::Heading(pulldown_cmark::HeadingLevel::H1, id, classes) => {
+            // Headings in footnotes are left out of the title and outline,
+            // as they are in fragments.
+            pulldown_cmark::Tag::Heading(pulldown_cmark::HeadingLevel::H1, id, classes)
+                if self.outside_footnote.is_none() =>
+            {
                 if !classes.is_empty() || id.is_some() {
-                    self.error("title IDs and classes are disallowed");
+                    self.error(
+                        DiagnosticKind::DisallowedAttribute,
+                        "title IDs and classes are disallowed",
+                    );
                 }
                 self.in_title = true;
             }
+            pulldown_cmark::Tag::Heading(level, id, classes)
+                if self.fragment || self.outside_footnote.is_some() =>
+            {
+                if !classes.is_empty() {
+                    self.error(
+                        DiagnosticKind::DisallowedAttribute,
+                        "heading classes are disallowed",
+                    );
+                }
+                push!(self, "<h{}", level as u8);
+                if let Some(id) = id {
+                    self.push_str(" id='");
+                    escape_html(self, id);
+                    self.push_str("'");
+                }
+                self.push_str(">");
+            }
             pulldown_cmark::Tag::Heading(level, id, classes) => {
                 if !classes.is_empty() {
-                    self.error("heading classes are disallowed");
+                    self.error(
+                        DiagnosticKind::DisallowedAttribute,
+                        "heading classes are disallowed",
+                    );
                 }
 
                 let mut level = level as u8;
@@ -160,9 +731,10 @@ impl<'a> Renderer<'a> {
 
                     if level != self.outline_level + 1 {
                         let outline_level = self.outline_level;
-                        self.error(format_args!(
-                            "heading level jump: {outline_level} to {level}"
-                        ));
+                        self.error(
+                            DiagnosticKind::Heading,
+                            format_args!("heading level jump: {outline_level} to {level}"),
+                        );
                         level = self.outline_level + 1;
                     }
                 }
@@ -177,11 +749,18 @@ impl<'a> Renderer<'a> {
                 if let Some(id) = id {
                     push!(self, "<h{level} id='");
                     escape_html(self, id);
-                    self.push_str("'><a href='#");
-                    escape_html(self, id);
-                    self.push_str("' class='anchor'></a>");
+                    self.push_str("'>");
+                    match self.options.anchor_style {
+                        AnchorStyle::Leading => self.anchor(id),
+                        AnchorStyle::WrapText => {
+                            self.push_str("<a href='#");
+                            escape_html(self, id);
+                            self.push_str("' class='anchor'>");
+                        }
+                        AnchorStyle::Trailing | AnchorStyle::None => {}
+                    }
                 } else {
-                    self.error("heading does not have id");
+                    self.error(DiagnosticKind::Heading, "heading does not have id");
                     push!(self, "<h{level}>");
                 }
 
@@ -214,14 +793,32 @@ impl<'a> Renderer<'a> {
             }
             pulldown_cmark::Tag::BlockQuote => self.push_str("<blockquote>"),
             pulldown_cmark::Tag::CodeBlock(kind) => {
-                self.push_str("<pre class='scode'><code>");
-
-                let language = match kind {
-                    pulldown_cmark::CodeBlockKind::Fenced(lang) if lang.is_empty() => None,
-                    pulldown_cmark::CodeBlockKind::Fenced(lang) => Some(lang),
-                    pulldown_cmark::CodeBlockKind::Indented => None,
+                let info = match &kind {
+                    pulldown_cmark::CodeBlockKind::Fenced(info) => &**info,
+                    pulldown_cmark::CodeBlockKind::Indented => "",
                 };
 
+                // The info string is a language followed by comma-separated flags.
+                let mut info = info.split(',');
+                let language = Some(info.next().unwrap().trim()).filter(|lang| !lang.is_empty());
+                let mut wrap = false;
+                for flag in info {
+                    match flag.trim() {
+                        "wrap" => wrap = true,
+                        flag => self.error(
+                            DiagnosticKind::CodeBlock,
+                            format_args!("unknown code block flag {flag}"),
+                        ),
+                    }
+                }
+
+                if wrap {
+                    self.push_str("<pre class='scode scode-wrap'><code>");
+                    self.used_classes.insert(Classes::Wrap);
+                } else {
+                    self.push_str("<pre class='scode'><code>");
+                }
+
                 fn event_text(
                     event: pulldown_cmark::Event<'_>,
                 ) -> Option<pulldown_cmark::CowStr<'_>> {
@@ -235,33 +832,59 @@ impl<'a> Renderer<'a> {
 
                 if let Some(language) = language {
                     let mut code = String::new();
-                    while let Some(part) = self.parser.next().and_then(event_text) {
+                    while let Some(part) = self.next_event().and_then(event_text) {
                         code.push_str(&part);
                     }
-                    self.syntax_highlight(&language, &code);
+                    self.syntax_highlight(language, &code);
                 } else {
-                    while let Some(part) = self.parser.next().and_then(event_text) {
+                    while let Some(part) = self.next_event().and_then(event_text) {
                         escape_html(self, &part);
                     }
                 }
 
                 self.push_str("</code></pre>");
             }
-            pulldown_cmark::Tag::List(Some(1)) => self.push_str("<ol>"),
             pulldown_cmark::Tag::List(Some(start)) => {
-                push!(self, "<ol start='{}'>", start);
+                self.lists.push((self.body.len() + "<ol".len(), false));
+                self.push_str("<ol");
+                if start != 1 {
+                    push!(self, " start='{}'", start);
+                }
+                if let Some(list_type) = self.list_type.take() {
+                    push!(self, " type='{}'", list_type);
+                }
+                self.push_str(">");
+            }
+            pulldown_cmark::Tag::List(None) => {
+                self.lists.push((self.body.len() + "<ul".len(), false));
+                self.push_str("<ul>");
             }
-            pulldown_cmark::Tag::List(None) => self.push_str("<ul>"),
             pulldown_cmark::Tag::Item => self.push_str("<li>"),
             pulldown_cmark::Tag::Emphasis => self.push_str("<em>"),
             pulldown_cmark::Tag::Strong => self.push_str("<strong>"),
             pulldown_cmark::Tag::Strikethrough => self.push_str("<del>"),
-            pulldown_cmark::Tag::Link(pulldown_cmark::LinkType::Email, ..) => {
-                self.error("email links are not supported yet");
+            pulldown_cmark::Tag::Link(pulldown_cmark::LinkType::Email, href, _) => {
+                self.external_link = false;
+                self.push_str("<a href='mailto:");
+                escape_href(self, &href);
+                self.push_str("'>");
+            }
+            pulldown_cmark::Tag::Link(
+                pulldown_cmark::LinkType::ReferenceUnknown
+                | pulldown_cmark::LinkType::CollapsedUnknown,
+                label,
+                _,
+            ) => {
+                self.error(
+                    DiagnosticKind::Link,
+                    format_args!("undefined link reference {label}"),
+                );
             }
             pulldown_cmark::Tag::Link(_type, href, title) => {
+                self.external_link = !self.options.external_link_icon.is_empty()
+                    && is_external(&href, &self.options.origin);
                 self.push_str("<a href='");
-                escape_href(self, &href);
+                self.push_url(&href);
                 if !title.is_empty() {
                     self.push_str("' title='");
                     escape_html(self, &title);
@@ -269,10 +892,36 @@ impl<'a> Renderer<'a> {
                 self.push_str("'>");
             }
             pulldown_cmark::Tag::Image(_, url, title) => {
+                // Image links don't get the external link icon.
+                self.external_link = false;
+
+                // Dimensions may be given as a trailing `{width=600 height=400}` in the title,
+                // so the browser can reserve space for the image before it loads.
+                let (title, attributes) = split_image_attributes(&title);
+                let mut dimensions = Vec::new();
+                for attribute in attributes.split_whitespace() {
+                    match attribute.split_once('=') {
+                        Some((name @ ("width" | "height"), value))
+                            if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) =>
+                        {
+                            dimensions.push((name, value));
+                        }
+                        _ => self.error(
+                            DiagnosticKind::Link,
+                            format_args!("invalid image attribute {attribute}"),
+                        ),
+                    }
+                }
+
+                let variants = self.options.image_variants.get(&*url);
+                if let Some(variants) = variants {
+                    self.push_image_sources(variants);
+                }
+
                 self.push_str("<img src='");
-                escape_href(self, &url);
+                self.push_url(&url);
                 self.push_str("' alt='");
-                while let Some(event) = self.parser.next() {
+                while let Some(event) = self.next_event() {
                     match event {
                         pulldown_cmark::Event::End(_) => break,
                         pulldown_cmark::Event::Text(text) => escape_html(self, &text),
@@ -282,32 +931,67 @@ impl<'a> Renderer<'a> {
                 }
                 if !title.is_empty() {
                     self.push_str("' title='");
-                    escape_html(self, &title);
+                    escape_html(self, title);
+                }
+                for (name, value) in dimensions {
+                    push!(self, "' {name}='{value}");
                 }
+                if self.options.image_loading_hints {
+                    if self.images == 0 {
+                        self.push_str("' loading='eager' fetchpriority='high");
+                    } else {
+                        self.push_str("' loading='lazy");
+                    }
+                }
+                self.images += 1;
                 self.push_str("'>");
+                if variants.is_some() {
+                    self.push_str("</picture>");
+                }
+            }
+            pulldown_cmark::Tag::FootnoteDefinition(label) => {
+                let body = mem::take(&mut self.body);
+                self.outside_footnote = Some((label.into_string(), body));
             }
-            // We do not enable this extension
-            pulldown_cmark::Tag::FootnoteDefinition(_) => unreachable!(),
         }
     }
 
     fn end_tag(&mut self, tag: pulldown_cmark::Tag<'a>) {
         match tag {
             pulldown_cmark::Tag::Paragraph => {
+                if let Some(start) = self.paragraph_start.take() {
+                    let id = self.paragraph_id(start);
+                    self.body.insert_str(start, &format!(" id='{id}'"));
+                }
                 self.push_str("</p>");
                 self.in_summary = false;
             }
-            pulldown_cmark::Tag::Heading(pulldown_cmark::HeadingLevel::H1, _id, _classes) => {
+            pulldown_cmark::Tag::Heading(pulldown_cmark::HeadingLevel::H1, _id, _classes)
+                if self.outside_footnote.is_none() =>
+            {
                 self.in_title = false;
             }
-            pulldown_cmark::Tag::Heading(level, _id, _classes) => {
+            pulldown_cmark::Tag::Heading(level, _id, _classes)
+                if self.fragment || self.outside_footnote.is_some() =>
+            {
+                push!(self, "</h{}>", level as u8);
+            }
+            pulldown_cmark::Tag::Heading(_level, id, _classes) => {
                 self.in_heading = false;
 
                 self.outline.push_str("</a>");
 
-                self.push_str("</");
-                push!(self, "{}", level);
-                self.push_str(">");
+                if let Some(id) = id {
+                    match self.options.anchor_style {
+                        AnchorStyle::Trailing => self.anchor(id),
+                        AnchorStyle::WrapText => self.push_str("</a>"),
+                        AnchorStyle::Leading | AnchorStyle::None => {}
+                    }
+                }
+
+                // The level may have been normalized when the heading was opened.
+                let level = self.outline_level;
+                push!(self, "</h{level}>");
             }
             pulldown_cmark::Tag::Table(_) => {
                 self.push_str("</tbody></table>");
@@ -326,25 +1010,141 @@ impl<'a> Renderer<'a> {
                 });
             }
             pulldown_cmark::Tag::BlockQuote => self.push_str("</blockquote>"),
-            pulldown_cmark::Tag::List(Some(_)) => self.push_str("</ol>"),
-            pulldown_cmark::Tag::List(None) => self.push_str("</ul>"),
+            pulldown_cmark::Tag::List(ordered) => {
+                let (start, task_list) = self.lists.pop().unwrap();
+                if task_list {
+                    self.body.insert_str(start, " class='task-list'");
+                }
+                self.push_str(if ordered.is_some() { "</ol>" } else { "</ul>" });
+            }
             pulldown_cmark::Tag::Item => self.push_str("</li>"),
             pulldown_cmark::Tag::Emphasis => self.push_str("</em>"),
             pulldown_cmark::Tag::Strong => self.push_str("</strong>"),
             pulldown_cmark::Tag::Strikethrough => self.push_str("</del>"),
-            pulldown_cmark::Tag::Link(_, _, _) => self.push_str("</a>"),
-            // We do not enable this extension
-            pulldown_cmark::Tag::FootnoteDefinition(_)
+            pulldown_cmark::Tag::Link(
+                pulldown_cmark::LinkType::ReferenceUnknown
+                | pulldown_cmark::LinkType::CollapsedUnknown,
+                _,
+                _,
+            ) => {}
+            pulldown_cmark::Tag::Link(_, _, _) => {
+                if self.external_link {
+                    self.external_link = false;
+                    let icon = &self.options.external_link_icon;
+                    self.push_str(icon);
+                }
+                self.push_str("</a>");
+            }
+            pulldown_cmark::Tag::FootnoteDefinition(_) => {
+                let (label, body) = self.outside_footnote.take().unwrap();
+                let html = mem::replace(&mut self.body, body);
+                if self
+                    .footnotes
+                    .iter()
+                    .any(|footnote| footnote.label == label)
+                {
+                    self.error(
+                        DiagnosticKind::Footnote,
+                        format_args!("footnote {label} is defined multiple times"),
+                    );
+                } else {
+                    self.footnotes.push(Footnote {
+                        label,
+                        html,
+                        offset: self.offset,
+                    });
+                }
+            }
             // We handle closing of these tags in the opening logic
-            | pulldown_cmark::Tag::Image(_, _, _)
-                | pulldown_cmark::Tag::CodeBlock(_)
-                => unreachable!(),
+            pulldown_cmark::Tag::Image(_, _, _) | pulldown_cmark::Tag::CodeBlock(_) => {
+                unreachable!()
+            }
+        }
+    }
+
+    fn footnote_reference(&mut self, label: &str) {
+        if self.in_title {
+            self.error(
+                DiagnosticKind::Footnote,
+                "footnotes cannot be referenced from the title",
+            );
+            return;
+        }
+        let next_number = self.footnote_numbers.len() + 1;
+        let (number, first) = match self.footnote_numbers.entry(label.to_owned()) {
+            hash_map::Entry::Occupied(entry) => (entry.get().0, false),
+            hash_map::Entry::Vacant(entry) => (entry.insert((next_number, self.offset)).0, true),
+        };
+        // Written straight to the body so that references in headings stay out of the outline.
+        let prefix = &self.options.footnote_prefix;
+        self.body.push_str("<sup");
+        if first {
+            push!(self.body, " id='{prefix}fnref-{number}'");
+        }
+        push!(
+            self.body,
+            "><a href='#{prefix}fn-{number}'>{number}</a></sup>"
+        );
+    }
+
+    /// Write the footnote definitions at the end of the body, in the order of their numbers.
+    fn push_footnotes(&mut self) {
+        let mut footnotes = mem::take(&mut self.footnotes);
+
+        let mut undefined = self
+            .footnote_numbers
+            .iter()
+            .filter(|(label, _)| !footnotes.iter().any(|footnote| footnote.label == **label))
+            .map(|(label, &(_, offset))| (offset, label.clone()))
+            .collect::<Vec<_>>();
+        undefined.sort_unstable();
+        for (offset, label) in undefined {
+            self.offset = offset;
+            self.error(
+                DiagnosticKind::Footnote,
+                format_args!("undefined footnote {label}"),
+            );
+        }
+
+        let mut numbered = Vec::new();
+        for footnote in footnotes.drain(..) {
+            if let Some(&(number, _)) = self.footnote_numbers.get(&footnote.label) {
+                numbered.push((number, footnote.html));
+            } else {
+                self.offset = footnote.offset;
+                self.error(
+                    DiagnosticKind::Footnote,
+                    format_args!("footnote {} is never referenced", footnote.label),
+                );
+            }
+        }
+        numbered.sort_unstable_by_key(|&(number, _)| number);
+
+        if numbered.is_empty() {
+            return;
+        }
+        let prefix = &self.options.footnote_prefix;
+        self.body.push_str("<ol class='footnotes'>");
+        for (number, html) in numbered {
+            push!(self.body, "<li id='{prefix}fn-{number}' value='{number}'>");
+            // Put the link back inside the last paragraph, so it isn't on a line of its own.
+            let back = format!(" <a href='#{prefix}fnref-{number}' class='footnote-back'>↩</a>");
+            if let Some(html) = html.strip_suffix("</p>") {
+                push!(self.body, "{}{back}</p>", html.trim_end());
+            } else {
+                push!(self.body, "{html}{back}");
+            }
+            self.body.push_str("</li>");
         }
+        self.body.push_str("</ol>");
     }
 
     fn syntax_highlight(&mut self, language: &str, code: &str) {
         let Some(syntax) = self.syntax_set.find_syntax_by_token(language) else {
-            self.error(format_args!("no known language {language}"));
+            self.error(
+                DiagnosticKind::CodeBlock,
+                format_args!("no known language {language}"),
+            );
             self.push_str(code);
             return;
         };
@@ -363,19 +1163,108 @@ impl<'a> Renderer<'a> {
         self.push_str(&generator.finalize());
     }
 
-    fn error(&mut self, msg: impl Display) {
+    fn anchor(&mut self, id: &str) {
+        self.push_str("<a href='#");
+        escape_html(self, id);
+        self.push_str("' class='anchor'>");
+        escape_html(self, &self.options.anchor_text);
+        self.push_str("</a>");
+    }
+
+    /// Generate a unique ID for the paragraph whose content starts just after `start`.
+    fn paragraph_id(&mut self, start: usize) -> String {
+        let content = &self.body[start + ">".len()..];
+
+        // 32-bit FNV-1a, which unlike `DefaultHasher` is guaranteed to be stable.
+        let hash = content.bytes().fold(0x811c_9dc5_u32, |hash, byte| {
+            (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+        });
+
+        let mut id = format!("p-{hash:08x}");
+        let mut n = 1;
+        while !self.paragraph_ids.insert(id.clone()) {
+            n += 1;
+            id = format!("p-{hash:08x}-{n}");
+        }
+        id
+    }
+
+    /// Push a link destination, rebasing it onto the base path if it is root-relative.
+    fn push_url(&mut self, url: &str) {
+        let options = self.options;
+        match url.strip_prefix('/') {
+            Some(path) if !options.base_path.is_empty() && !path.starts_with('/') => {
+                escape_href(self, &options.base_path);
+                escape_href(self, path);
+            }
+            _ => escape_href(self, url),
+        }
+    }
+
+    /// Open a `<picture>` offering the given variants of an image, grouped by type.
+    fn push_image_sources(&mut self, variants: &[ImageVariant]) {
+        let mut types = Vec::new();
+        for variant in variants {
+            let ty = image_type(&variant.src);
+            if !types.contains(&ty) {
+                types.push(ty);
+            }
+        }
+
+        self.push_str("<picture>");
+        for ty in types {
+            self.push_str("<source");
+            if let Some(ty) = ty {
+                push!(self, " type='{ty}'");
+            }
+            self.push_str(" srcset='");
+            let variants = variants.iter().filter(|v| image_type(&v.src) == ty);
+            for (i, variant) in variants.enumerate() {
+                if i != 0 {
+                    self.push_str(", ");
+                }
+                self.push_url(&variant.src);
+                push!(self, " {}w", variant.width);
+            }
+            self.push_str("'>");
+        }
+    }
+
+    fn error(&mut self, kind: DiagnosticKind, msg: impl Display) {
+        let message = msg.to_string();
         self.push_str("<span style='color:red'>");
-        push!(self, "{}", msg);
+        // Messages quote the source, which may not contain HTML when `raw_html` is off.
+        escape_html(&mut *self, &message);
         self.push_str("</span>");
+
+        let before = &self.source[..self.offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        self.diagnostics.push(Diagnostic {
+            kind,
+            message,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        });
     }
 
-    fn push_summary(&mut self, s: &str) {
+    /// Push plain text to the summary or the plain text title, if writing to either.
+    fn push_text(&mut self, s: &str) {
         if self.in_summary {
             self.summary.push_str(s);
         }
+        if self.in_title {
+            self.title_text.push_str(s);
+        }
     }
 }
 
+struct Footnote {
+    label: String,
+    html: String,
+    /// The position in the source of the definition.
+    offset: usize,
+}
+
 impl PushStr for Renderer<'_> {
     fn push_str(&mut self, s: &str) {
         if self.in_title {
@@ -389,6 +1278,85 @@ impl PushStr for Renderer<'_> {
     }
 }
 
+/// Split inline code of the form `[lang] code` into its language and the code itself.
+///
+/// Brackets only contain a language when they contain a language name and are followed by a space,
+/// which separates them from the code, so `[1, 2]` and `[u8]` are part of the code.
+/// To write code that begins with something that does look like one, such as `[a] b`,
+/// prefix it with an empty `[]`, which is removed along with a space after it.
+fn inline_code_language(text: &str) -> (Option<&str>, &str) {
+    let is_language = |language: &str| {
+        !language.is_empty()
+            && language
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "+#-_.".contains(c))
+    };
+    if let Some(code) = text.strip_prefix("[]").filter(|code| !code.is_empty()) {
+        return (None, code.strip_prefix(' ').unwrap_or(code));
+    }
+    match text.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+        Some((language, code)) if is_language(language) => match code.strip_prefix(' ') {
+            Some(code) => (Some(language), code),
+            None => (None, text),
+        },
+        _ => (None, text),
+    }
+}
+
+/// The MIME type of an image, guessed from its extension.
+fn image_type(src: &str) -> Option<&'static str> {
+    let (_, extension) = src.rsplit_once('.')?;
+    Some(match &*extension.to_ascii_lowercase() {
+        "avif" => "image/avif",
+        "webp" => "image/webp",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        _ => return None,
+    })
+}
+
+/// Whether a link goes to another site: it is absolute, and not to `origin` unless that is empty.
+/// Protocol-relative links are to `origin` if its host is the same.
+fn is_external(href: &str, origin: &str) -> bool {
+    if !["http://", "https://", "//"]
+        .iter()
+        .any(|prefix| href.starts_with(prefix))
+    {
+        return false;
+    }
+    let is_under = |origin: &str| {
+        href.get(..origin.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(origin))
+            && matches!(
+                href.as_bytes().get(origin.len()),
+                None | Some(b'/' | b'?' | b'#')
+            )
+    };
+    let host = origin.find("//").map_or("", |i| &origin[i..]);
+    origin.is_empty() || !(is_under(origin) || href.starts_with("//") && is_under(host))
+}
+
+/// Split an image title into the title itself and the contents of a trailing `{...}` block.
+fn split_image_attributes(title: &str) -> (&str, &str) {
+    title
+        .strip_suffix('}')
+        .and_then(|rest| rest.rsplit_once('{'))
+        .map_or((title, ""), |(title, attributes)| {
+            (title.trim_end(), attributes)
+        })
+}
+
+/// Valid values of the `type` attribute of `<ol>`.
+const LIST_TYPES: [&str; 5] = ["1", "a", "A", "i", "I"];
+
+/// Parse a list type hint of the form `<!-- type=a -->`, returning the type.
+fn list_type_hint(html: &str) -> Option<&str> {
+    let hint = html.trim().strip_prefix("<!--")?.strip_suffix("-->")?;
+    hint.trim().strip_prefix("type=")
+}
+
 struct TableAlignments(Vec<pulldown_cmark::Alignment>);
 
 impl TableAlignments {
@@ -428,29 +1396,38 @@ impl Hash for TableAlignments {
 #[derive(PartialEq, Eq, Hash)]
 enum Classes {
     Table(TableAlignments),
+    /// Code blocks whose lines are soft-wrapped instead of scrolling horizontally.
+    Wrap,
 }
 
 impl Classes {
     fn write_definition(&self, buf: &mut impl PushStr) {
         match self {
             Self::Table(alignments) => {
-                for (i, alignment) in alignments.0.iter().copied().enumerate() {
-                    if alignment == pulldown_cmark::Alignment::None {
+                // Columns with the same alignment share a rule.
+                for (alignment, value) in [
+                    (pulldown_cmark::Alignment::Left, "left"),
+                    (pulldown_cmark::Alignment::Center, "center"),
+                    (pulldown_cmark::Alignment::Right, "right"),
+                ] {
+                    let mut columns = (alignments.0.iter().enumerate())
+                        .filter(|&(_, &column_alignment)| column_alignment == alignment)
+                        .peekable();
+                    if columns.peek().is_none() {
                         continue;
                     }
-                    buf.push_str(".");
-                    alignments.write_class_name(buf);
-                    push!(buf, " td:nth-child({})", i + 1);
-                    buf.push_str("{text-align:");
-                    buf.push_str(match alignment {
-                        pulldown_cmark::Alignment::None => unreachable!(),
-                        pulldown_cmark::Alignment::Left => "left",
-                        pulldown_cmark::Alignment::Center => "center",
-                        pulldown_cmark::Alignment::Right => "right",
-                    });
-                    buf.push_str("}");
+                    for (n, (i, _)) in columns.enumerate() {
+                        if n != 0 {
+                            buf.push_str(",");
+                        }
+                        buf.push_str(".");
+                        alignments.write_class_name(buf);
+                        push!(buf, " td:nth-child({})", i + 1);
+                    }
+                    push!(buf, "{{text-align:{value}}}");
                 }
             }
+            Self::Wrap => buf.push_str(".scode-wrap{white-space:pre-wrap;overflow-wrap:anywhere}"),
         }
     }
 }
@@ -480,9 +1457,8 @@ mod tests {
         Classes::Table(class).write_definition(&mut buf);
         let css = concat!(
             ".tlnrcr td:nth-child(1){text-align:left}",
-            ".tlnrcr td:nth-child(3){text-align:right}",
             ".tlnrcr td:nth-child(4){text-align:center}",
-            ".tlnrcr td:nth-child(5){text-align:right}",
+            ".tlnrcr td:nth-child(3),.tlnrcr td:nth-child(5){text-align:right}",
         );
         assert_eq!(buf, css);
     }
@@ -495,6 +1471,42 @@ mod tests {
         markdown.body
     }
 
+    #[test]
+    fn render_html() {
+        assert_eq!(super::render_html("a *b*").unwrap(), "<p>a <em>b</em></p>");
+        assert_eq!(
+            super::render_html("# a\n\nb").unwrap(),
+            "<h1>a</h1><p>b</p>"
+        );
+        assert_eq!(
+            super::render_html("## a\n\n### b {#c}").unwrap(),
+            "<h2>a</h2><h3 id='c'>b</h3>"
+        );
+        assert!(super::render_html("[a][b]").is_err());
+
+        let render = RenderHtml::new().smart_punctuation(false);
+        assert_eq!(render.render("\"a\"").unwrap(), "<p>&quot;a&quot;</p>");
+        let render = RenderHtml::new().summary(true);
+        assert_eq!(render.render("# t\n\na *b*\n\nc").unwrap(), "a b");
+        let render = RenderHtml::new().outline(true);
+        assert_eq!(
+            render.render("## a {#a}").unwrap(),
+            "<nav><ul><li><a href='#a'>a</a></li></ul></nav>\
+            <h2 id='a'><a href='#a' class='anchor'></a>a</h2>"
+        );
+    }
+
+    #[test]
+    fn scope_css() {
+        assert_eq!(
+            super::scope_css(
+                "/* theme */\n.scode {\n color: red;\n}\n.sa, .sb.sc {\n color: blue;\n}",
+                ".t",
+            ),
+            "/* theme */\n.t .scode {\n color: red;\n}\n.t .sa, .t .sb.sc {\n color: blue;\n}\n",
+        );
+    }
+
     #[test]
     fn empty() {
         assert_eq!(just_body(""), "");
@@ -515,9 +1527,11 @@ mod tests {
             parse("# foo bar"),
             Markdown {
                 title: "foo bar".to_owned(),
+                title_text: "foo bar".to_owned(),
                 body: String::new(),
                 summary: String::new(),
                 outline: String::new(),
+                diagnostics: Vec::new(),
             },
         );
         assert_eq!(
@@ -533,6 +1547,7 @@ mod tests {
             ),
             Markdown {
                 title: "the <em>title</em>".to_owned(),
+                title_text: "the title".to_owned(),
                 body: "\
                     <h2 id='a'><a href='#a' class='anchor'></a>a</h2>\
                         <h3 id='b'><a href='#b' class='anchor'></a>b</h3>\
@@ -554,8 +1569,212 @@ mod tests {
                     </ul>\
                 "
                 .to_owned(),
+                diagnostics: Vec::new(),
+            },
+        );
+    }
+
+    #[test]
+    fn heading_level_jump() {
+        assert_eq!(
+            parse("## a { #a }\n#### b { #b }"),
+            Markdown {
+                title: String::new(),
+                title_text: String::new(),
+                body: "\
+                    <h2 id='a'><a href='#a' class='anchor'></a>a</h2>\
+                    <span style='color:red'>heading level jump: 2 to 4</span>\
+                    <h3 id='b'><a href='#b' class='anchor'></a>b</h3>\
+                "
+                .to_owned(),
+                summary: String::new(),
+                outline: "\
+                    <ul><li><a href='#a'>a</a>\
+                    <ul><li><a href='#b'>b</a></li></ul>\
+                    </li></ul>\
+                "
+                .to_owned(),
+                diagnostics: vec![Diagnostic {
+                    kind: DiagnosticKind::Heading,
+                    message: "heading level jump: 2 to 4".to_owned(),
+                    line: 2,
+                    column: 1,
+                }],
+            },
+        );
+    }
+
+    #[test]
+    fn heading_anchor() {
+        let options = Options {
+            anchor_style: AnchorStyle::Trailing,
+            anchor_text: "¶".to_owned(),
+            ..Options::default()
+        };
+        assert_eq!(
+            super::parse("## a { #a }\n## b", &options),
+            Markdown {
+                title: String::new(),
+                title_text: String::new(),
+                body: "\
+                    <h2 id='a'>a<a href='#a' class='anchor'>¶</a></h2>\
+                    <span style='color:red'>heading does not have id</span><h2>b</h2>\
+                "
+                .to_owned(),
+                summary: String::new(),
+                outline: "<ul><li><a href='#a'>a</a></li><li><a href='#'>b</a></li></ul>"
+                    .to_owned(),
+                diagnostics: vec![Diagnostic {
+                    kind: DiagnosticKind::Heading,
+                    message: "heading does not have id".to_owned(),
+                    line: 2,
+                    column: 1,
+                }],
+            },
+        );
+
+        let body = |anchor_style| {
+            let options = Options {
+                anchor_style,
+                anchor_text: "#".to_owned(),
+                ..Options::default()
+            };
+            let markdown = super::parse("## *a* { #a }", &options);
+            assert_eq!(
+                markdown.outline,
+                "<ul><li><a href='#a'><em>a</em></a></li></ul>"
+            );
+            markdown.body
+        };
+        assert_eq!(
+            body(AnchorStyle::Leading),
+            "<h2 id='a'><a href='#a' class='anchor'>#</a><em>a</em></h2>",
+        );
+        assert_eq!(
+            body(AnchorStyle::Trailing),
+            "<h2 id='a'><em>a</em><a href='#a' class='anchor'>#</a></h2>",
+        );
+        assert_eq!(body(AnchorStyle::None), "<h2 id='a'><em>a</em></h2>");
+        assert_eq!(
+            body(AnchorStyle::WrapText),
+            "<h2 id='a'><a href='#a' class='anchor'><em>a</em></a></h2>",
+        );
+    }
+
+    #[test]
+    fn footnotes() {
+        assert_eq!(
+            parse(
+                "\
+                    a[^x] b[^1]\n\
+                    \n\
+                    ## c[^x] { #c }\n\
+                    \n\
+                    [^1]: one\n\
+                    [^x]: two\n\
+                ",
+            ),
+            Markdown {
+                title: String::new(),
+                title_text: String::new(),
+                body: "\
+                    <p>a<sup id='fnref-1'><a href='#fn-1'>1</a></sup> \
+                    b<sup id='fnref-2'><a href='#fn-2'>2</a></sup></p>\
+                    <h2 id='c'><a href='#c' class='anchor'></a>c<sup><a href='#fn-1'>1</a></sup></h2>\
+                    <ol class='footnotes'>\
+                        <li id='fn-1' value='1'><p>two <a href='#fnref-1' class='footnote-back'>↩</a></p></li>\
+                        <li id='fn-2' value='2'><p>one <a href='#fnref-2' class='footnote-back'>↩</a></p></li>\
+                    </ol>\
+                "
+                .to_owned(),
+                summary: "a b".to_owned(),
+                outline: "<ul><li><a href='#c'>c</a></li></ul>".to_owned(),
+                diagnostics: Vec::new(),
+            },
+        );
+        assert_eq!(
+            parse("a[^x]\n\n[^y]: b").diagnostics,
+            [
+                Diagnostic {
+                    kind: DiagnosticKind::Footnote,
+                    message: "undefined footnote x".to_owned(),
+                    line: 1,
+                    column: 2,
+                },
+                Diagnostic {
+                    kind: DiagnosticKind::Footnote,
+                    message: "footnote y is never referenced".to_owned(),
+                    line: 3,
+                    column: 1,
+                },
+            ],
+        );
+
+        // Headings in footnotes don't become the title or go in the outline.
+        assert_eq!(
+            just_body("a[^1] b[^2]\n\n[^1]: # c\n\n[^2]: ## d"),
+            "\
+                <p>a<sup id='fnref-1'><a href='#fn-1'>1</a></sup> \
+                b<sup id='fnref-2'><a href='#fn-2'>2</a></sup></p>\
+                <ol class='footnotes'>\
+                    <li id='fn-1' value='1'><h1>c</h1> <a href='#fnref-1' class='footnote-back'>↩</a></li>\
+                    <li id='fn-2' value='2'><h2>d</h2> <a href='#fnref-2' class='footnote-back'>↩</a></li>\
+                </ol>\
+            ",
+        );
+
+        let options = Options {
+            footnote_prefix: "p-".to_owned(),
+            ..Options::default()
+        };
+        assert_eq!(
+            super::parse("a[^1]\n\n[^1]: b", &options).body,
+            "\
+                <p>a<sup id='p-fnref-1'><a href='#p-fn-1'>1</a></sup></p>\
+                <ol class='footnotes'>\
+                    <li id='p-fn-1' value='1'><p>b <a href='#p-fnref-1' class='footnote-back'>↩</a></p></li>\
+                </ol>\
+            ",
+        );
+    }
+
+    #[test]
+    fn task_list() {
+        assert_eq!(
+            parse("- [ ] a\n- [x] b\n  - [X] c"),
+            Markdown {
+                title: String::new(),
+                title_text: String::new(),
+                body: "\
+                    <ul class='task-list'>\
+                        <li><input type='checkbox' disabled>a</li>\
+                        <li><input type='checkbox' disabled checked>b\
+                            <ul class='task-list'>\
+                                <li><input type='checkbox' disabled checked>c</li>\
+                            </ul>\
+                        </li>\
+                    </ul>\
+                "
+                .to_owned(),
+                summary: String::new(),
+                outline: String::new(),
+                diagnostics: Vec::new(),
             },
         );
+        assert_eq!(
+            parse("- [x] a\n\n  b").body,
+            "<ul class='task-list'>\
+                <li><input type='checkbox' disabled checked><p>a</p><p>b</p></li>\
+            </ul>",
+        );
+        // Only lists that directly contain checkboxes get the class.
+        assert_eq!(
+            parse("1. [ ] a\n2. b\n   - c").body,
+            "<ol class='task-list'>\
+                <li><input type='checkbox' disabled>a</li>\
+                <li>b<ul><li>c</li></ul></li>\
+            </ol>",
+        );
     }
 
     #[test]
@@ -637,7 +1856,7 @@ mod tests {
         );
         assert_eq!(
             just_body("`[rs] let foo = 5;`"),
-            "<p><code class='scode'><span class=\"ssource srust\"> \
+            "<p><code class='scode'><span class=\"ssource srust\">\
                 <span class=\"sstorage stype srust\">let</span> \
                 foo \
                 <span class=\"skeyword soperator srust\">=</span> \
@@ -645,6 +1864,23 @@ mod tests {
                 <span class=\"spunctuation sterminator srust\">;</span>\
             </span></code></p>",
         );
+        assert_eq!(
+            just_body("`[1, 2]`"),
+            "<p><code class='scode'>[1, 2]</code></p>"
+        );
+        assert_eq!(
+            just_body("`[][a]`"),
+            "<p><code class='scode'>[a]</code></p>"
+        );
+        assert_eq!(just_body("`[]`"), "<p><code class='scode'>[]</code></p>");
+        assert_eq!(
+            just_body("`[u8]`"),
+            "<p><code class='scode'>[u8]</code></p>"
+        );
+        assert_eq!(
+            just_body("`[] [a]`"),
+            "<p><code class='scode'>[a]</code></p>"
+        );
     }
 
     #[test]
@@ -675,6 +1911,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn block_code_wrap() {
+        assert_eq!(
+            just_body("```,wrap\ncode\n```"),
+            "\
+                <pre class='scode scode-wrap'><code>code\n</code></pre>\
+                <style>.scode-wrap{white-space:pre-wrap;overflow-wrap:anywhere}</style>\
+            "
+        );
+        assert!(
+            just_body("```rs,wrap\nlet x = 5;\n```").starts_with("<pre class='scode scode-wrap'>")
+        );
+        assert!(!just_body("```rs\nlet x = 5;\n```").contains("scode-wrap"));
+        assert_eq!(
+            just_body("```,bogus\ncode\n```"),
+            "\
+                <span style='color:red'>unknown code block flag bogus</span>\
+                <pre class='scode'><code>code\n</code></pre>\
+            "
+        );
+    }
+
     #[test]
     fn lists() {
         assert_eq!(
@@ -689,6 +1947,129 @@ mod tests {
             just_body("- item\n- item"),
             "<ul><li>item</li><li>item</li></ul>"
         );
+        assert_eq!(
+            just_body("0. Rust\n1. other languages"),
+            "<ol start='0'><li>Rust</li><li>other languages</li></ol>"
+        );
+        // Without hints enabled, the comment is passed through unchanged.
+        assert_eq!(
+            just_body("<!-- type=a -->\n1. Rust"),
+            "<!-- type=a -->\n<ol><li>Rust</li></ol>"
+        );
+    }
+
+    #[test]
+    fn list_type_hints() {
+        let options = Options {
+            list_type_hints: true,
+            ..Options::default()
+        };
+        let body = |input| super::parse(input, &options).body;
+
+        assert_eq!(
+            body("<!-- type=a -->\n1. Rust\n1. other languages"),
+            "<ol type='a'><li>Rust</li><li>other languages</li></ol>"
+        );
+        assert_eq!(
+            body("<!-- type=I -->\n3. Rust"),
+            "<ol start='3' type='I'><li>Rust</li></ol>"
+        );
+        assert_eq!(
+            body("<!-- type=a -->\n1. a\n\n<!-- comment -->\n1. b"),
+            "<ol type='a'><li>a</li></ol><!-- comment -->\n<ol><li>b</li></ol>"
+        );
+        assert_eq!(
+            body("<!-- type=x -->\n1. Rust"),
+            "<span style='color:red'>invalid list type x</span><ol><li>Rust</li></ol>"
+        );
+        assert_eq!(
+            body("<!-- type=a -->\n- Rust"),
+            "\
+                <span style='color:red'>list type hint is not directly followed by an ordered list</span>\
+                <ul><li>Rust</li></ul>\
+            "
+        );
+    }
+
+    #[test]
+    fn disabled_features() {
+        let options = Options {
+            features: Features {
+                tables: false,
+                raw_html: false,
+                ..Features::default()
+            },
+            ..Options::default()
+        };
+        let body = |input| super::parse(input, &options).body;
+
+        assert_eq!(
+            body("| a | b |\n| - | - |\n| c | d |"),
+            "<p>| a | b | | - | - | | c | d |</p>"
+        );
+        assert_eq!(
+            body("<script>alert(1)</script>"),
+            "&lt;script&gt;alert(1)&lt;/script&gt;"
+        );
+        assert_eq!(body("a <b>b</b>"), "<p>a &lt;b&gt;b&lt;/b&gt;</p>");
+        assert_eq!(body("~~a~~"), "<p><del>a</del></p>");
+    }
+
+    #[test]
+    fn feature_overrides() {
+        let overrides: FeatureOverrides =
+            serde_json::from_str(r#"{ "tables": true, "smart-punctuation": false }"#).unwrap();
+        let site = Features {
+            tables: false,
+            ..Features::default()
+        };
+        let features = site.with_overrides(overrides);
+        assert!(features.tables);
+        assert!(!features.smart_punctuation);
+        assert!(features.strikethrough);
+
+        assert!(serde_json::from_str::<FeatureOverrides>(r#"{ "table": true }"#).is_err());
+    }
+
+    #[test]
+    fn base_path() {
+        let options = Options {
+            base_path: "/project/".to_owned(),
+            ..Options::default()
+        };
+        let body = |input| super::parse(input, &options).body;
+
+        assert_eq!(
+            body("[a](/blog/) [b](c) [d](//e.com/)"),
+            "<p><a href='/project/blog/'>a</a> <a href='c'>b</a> <a href='//e.com/'>d</a></p>"
+        );
+        assert_eq!(
+            body("![a](/static/a.png)"),
+            "<p><img src='/project/static/a.png' alt='a'></p>"
+        );
+    }
+
+    #[test]
+    fn paragraph_ids() {
+        let options = Options {
+            paragraph_ids: true,
+            ..Options::default()
+        };
+        let body = |input| super::parse(input, &options).body;
+
+        assert_eq!(body("a"), "<p id='p-e40c292c'>a</p>");
+        assert_eq!(
+            body("b\n\na"),
+            "<p id='p-e70c2de5'>b</p><p id='p-e40c292c'>a</p>"
+        );
+        assert_eq!(
+            body("a\n\na"),
+            "<p id='p-e40c292c'>a</p><p id='p-e40c292c-2'>a</p>"
+        );
+        assert_eq!(
+            body("> *a*"),
+            "<blockquote><p id='p-a9262907'><em>a</em></p></blockquote>"
+        );
     }
 
     #[test]
@@ -712,6 +2093,85 @@ mod tests {
         );
     }
 
+    #[test]
+    fn reference_links() {
+        assert_eq!(
+            just_body("[x][r] [y][]\n\n[r]: https://example.com\n[y]: /y"),
+            "<p><a href='https://example.com'>x</a> <a href='/y'>y</a></p>",
+        );
+        assert_eq!(
+            just_body("[x][missing]"),
+            "<p><span style='color:red'>undefined link reference missing</span>x</p>",
+        );
+        assert_eq!(
+            just_body("[y][]"),
+            "<p><span style='color:red'>undefined link reference y</span>y</p>",
+        );
+        assert_eq!(
+            just_body("[x][<script>]"),
+            "<p><span style='color:red'>undefined link reference &lt;script&gt;</span>x</p>",
+        );
+        assert_eq!(just_body("[x]"), "<p>[x]</p>");
+    }
+
+    #[test]
+    fn external_link_icon() {
+        let options = Options {
+            external_link_icon: "<span class='ext'>↗</span>".to_owned(),
+            ..Options::default()
+        };
+        let body = |input| super::parse(input, &options).body;
+
+        assert_eq!(
+            body("[a](https://example.com) [b](/b) [c](#c)"),
+            "<p><a href='https://example.com'>a<span class='ext'>↗</span></a> \
+            <a href='/b'>b</a> <a href='#c'>c</a></p>",
+        );
+        assert_eq!(
+            body("[![a](a.png)](https://example.com)"),
+            "<p><a href='https://example.com'><img src='a.png' alt='a'></a></p>",
+        );
+
+        let options = Options {
+            origin: "https://example.com".to_owned(),
+            ..options
+        };
+        let body = |input| super::parse(input, &options).body;
+        assert_eq!(
+            body("[a](https://example.com/a) [b](//example.com) [c](https://example.com.evil)"),
+            "<p><a href='https://example.com/a'>a</a> <a href='//example.com'>b</a> \
+            <a href='https://example.com.evil'>c<span class='ext'>↗</span></a></p>",
+        );
+    }
+
+    #[test]
+    fn email_links() {
+        assert_eq!(
+            just_body("<me@example.com>"),
+            "<p><a href='mailto:me@example.com'>me@example.com</a></p>",
+        );
+    }
+
+    #[test]
+    fn escaped_urls() {
+        assert_eq!(
+            just_body("[a](<a b.html?c=d&e=f'g#h>)"),
+            "<p><a href='a%20b.html?c=d&amp;e=f&#x27;g#h'>a</a></p>",
+        );
+        assert_eq!(
+            just_body("[a](/ä%20ö)"),
+            "<p><a href='/%C3%A4%20%C3%B6'>a</a></p>",
+        );
+        assert_eq!(
+            just_body("![a](<my photo's.png>)"),
+            "<p><img src='my%20photo&#x27;s.png' alt='a'></p>",
+        );
+        assert_eq!(
+            just_body("![a](日本.png)"),
+            "<p><img src='%E6%97%A5%E6%9C%AC.png' alt='a'></p>",
+        );
+    }
+
     #[test]
     fn images() {
         assert_eq!(
@@ -720,6 +2180,67 @@ mod tests {
         );
     }
 
+    #[test]
+    fn image_dimensions() {
+        assert_eq!(
+            just_body("![a](a.png \"{width=600 height=400}\")"),
+            "<p><img src='a.png' alt='a' width='600' height='400'></p>",
+        );
+        assert_eq!(
+            just_body("![a](a.png \"A title { width=600 }\")"),
+            "<p><img src='a.png' alt='a' title='A title' width='600'></p>",
+        );
+        assert_eq!(
+            just_body("![a](a.png \"{width=big}\")"),
+            "<p><span style='color:red'>invalid image attribute width=big</span>\
+            <img src='a.png' alt='a'></p>",
+        );
+    }
+
+    #[test]
+    fn image_loading_hints() {
+        let options = Options {
+            image_loading_hints: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            super::parse("![a](a.png)\n\n![b](b.png)", &options).body,
+            "\
+                <p><img src='a.png' alt='a' loading='eager' fetchpriority='high'></p>\
+                <p><img src='b.png' alt='b' loading='lazy'></p>\
+            ",
+        );
+    }
+
+    #[test]
+    fn image_variants() {
+        let variant = |src: &str, width| ImageVariant {
+            src: src.to_owned(),
+            width,
+        };
+        let options = Options {
+            image_variants: ImageVariants::from([(
+                "photo.jpg".to_owned(),
+                vec![
+                    variant("photo-640.webp", 640),
+                    variant("photo-640.jpg", 640),
+                    variant("photo-1280.webp", 1280),
+                ],
+            )]),
+            ..Options::default()
+        };
+        assert_eq!(
+            super::parse("![a](photo.jpg)\n\n![b](other.jpg)", &options).body,
+            "\
+                <p><picture>\
+                <source type='image/webp' srcset='photo-640.webp 640w, photo-1280.webp 1280w'>\
+                <source type='image/jpeg' srcset='photo-640.jpg 640w'>\
+                <img src='photo.jpg' alt='a'></picture></p>\
+                <p><img src='other.jpg' alt='b'></p>\
+            ",
+        );
+    }
+
     #[track_caller]
     fn just_summary(input: &str) -> String {
         let markdown = parse(input);
@@ -733,29 +2254,54 @@ mod tests {
         assert_eq!(just_summary("lorem ipsum dolor"), "lorem ipsum dolor");
         assert_eq!(just_summary("lorem\nipsum  \ndolor"), "lorem ipsum dolor");
         assert_eq!(
-            just_summary("`[rs]lorem` **ipsum** _dolor_"),
+            just_summary("`[rs] lorem` **ipsum** _dolor_"),
             "lorem ipsum dolor"
         );
         assert_eq!(just_summary("lorem ipsum\n\ndolor sit amet"), "lorem ipsum");
     }
 
-    use super::parse;
+    fn parse(input: &str) -> Markdown {
+        super::parse(input, &Options::default())
+    }
+
+    use super::AnchorStyle;
     use super::Classes;
+    use super::Diagnostic;
+    use super::DiagnosticKind;
+    use super::FeatureOverrides;
+    use super::Features;
+    use super::ImageVariant;
+    use super::ImageVariants;
     use super::Markdown;
+    use super::Options;
+    use super::RenderHtml;
     use super::TableAlignments;
     use pulldown_cmark::Alignment;
 }
 
+use crate::util::asset;
+use crate::util::asset::Asset;
 use crate::util::push_str::escape_href;
 use crate::util::push_str::escape_html;
 use crate::util::push_str::push;
 use crate::util::push_str::PushStr;
+use anyhow::Context as _;
 use once_cell::sync::Lazy;
+use serde::Deserialize;
 use serde::Serialize;
+use std::collections::hash_map;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fmt::Display;
+use std::fs;
 use std::hash::Hash;
 use std::hash::Hasher;
+use std::mem;
+use std::path::Path;
+use std::path::PathBuf;
+use std::rc::Rc;
 use syntect::highlighting::Theme;
+use syntect::highlighting::ThemeSet;
 use syntect::parsing::SyntaxSet;
 use syntect::util::LinesWithEndings;
@@ -1,13 +1,16 @@
+/// The npm package the minifiers are run from, including its `node_modules`.
+pub(crate) const DIR: &str = "builder/js";
+
 pub(crate) fn asset() -> impl Asset<Output = ()> {
-    asset::FsPath::new("./builder/js/package.json")
+    asset::FsPath::new(Path::new(DIR).join("package.json"))
         .map(|()| log_errors(npm_install()))
-        .modifies_path("./builder/js/package-lock.json")
+        .modifies_path(Path::new(DIR).join("package-lock.json"))
 }
 
 fn npm_install() -> anyhow::Result<()> {
     let status = process::Command::new("npm")
         .arg("install")
-        .current_dir("./builder/js")
+        .current_dir(DIR)
         .status()
         .context("failed to run `npm install`")?;
 
@@ -19,11 +22,23 @@ fn npm_install() -> anyhow::Result<()> {
     Ok(())
 }
 
-pub(crate) fn minify(file_type: FileType, s: &mut String) {
+#[derive(Clone, Copy, Hash)]
+pub(crate) struct Options {
+    /// Files smaller than this many bytes are left as-is,
+    /// since spawning a minifier for them costs more than it saves.
+    pub threshold: usize,
+    /// How long to wait for a minifier before killing it and leaving the input unminified.
+    pub timeout: Duration,
+}
+
+pub(crate) fn minify(options: Options, file_type: FileType, s: &mut String) {
+    if s.len() < options.threshold {
+        return;
+    }
     let res = match file_type {
-        FileType::Html => html(s),
-        FileType::Css => css(s),
-        FileType::Js => js(s),
+        FileType::Html => html(s, options.timeout),
+        FileType::Css => css(s, options.timeout),
+        FileType::Js => js(s, options.timeout),
     };
     match res {
         Ok(minified) => *s = minified,
@@ -38,7 +53,7 @@ pub(crate) enum FileType {
     Js,
 }
 
-fn html(src: &str) -> anyhow::Result<String> {
+fn html(src: &str, timeout: Duration) -> anyhow::Result<String> {
     pipe(
         process::Command::new("npx")
             .arg("html-minifier-terser")
@@ -56,24 +71,26 @@ fn html(src: &str) -> anyhow::Result<String> {
             .arg("--remove-tag-whitespace")
             .arg("--sort-attributes")
             .arg("--sort-class-name")
-            .current_dir("./builder/js"),
+            .current_dir(DIR),
         src,
+        timeout,
     )
     .context("failed to minify HTML with html-minifier-terser")
 }
 
-fn css(src: &str) -> anyhow::Result<String> {
+fn css(src: &str, timeout: Duration) -> anyhow::Result<String> {
     pipe(
         process::Command::new("npx")
             .arg("cleancss")
             .arg("-O2")
-            .current_dir("./builder/js"),
+            .current_dir(DIR),
         src,
+        timeout,
     )
     .context("failed to minify CSS with cleancss")
 }
 
-fn js(src: &str) -> anyhow::Result<String> {
+fn js(src: &str, timeout: Duration) -> anyhow::Result<String> {
     pipe(
         process::Command::new("npx")
             .arg("terser")
@@ -81,33 +98,38 @@ fn js(src: &str) -> anyhow::Result<String> {
             .arg("toplevel")
             .arg("--mangle-props")
             .arg("--compress")
-            .current_dir("./builder/js"),
+            .current_dir(DIR),
         src,
+        timeout,
     )
     .context("failed to minify JS with terser")
 }
 
-fn pipe(command: &mut process::Command, input: &str) -> anyhow::Result<String> {
+fn pipe(command: &mut process::Command, input: &str, timeout: Duration) -> anyhow::Result<String> {
     let mut child = command
         .stdin(process::Stdio::piped())
         .stdout(process::Stdio::piped())
         .spawn()
         .context("failed to spawn child process")?;
 
-    child
-        .stdin
-        .take()
-        .unwrap()
-        .write_all(input.as_bytes())
-        .context("failed to write to child process' stdin")?;
+    let stdin = child.stdin.take().unwrap();
+    let stdout = child.stdout.take().unwrap();
+    let input = input.to_owned();
 
-    let mut output = String::new();
-    child
-        .stdout
-        .take()
-        .unwrap()
-        .read_to_string(&mut output)
-        .context("failed to read from child process' stdout")?;
+    // Communicate with the child on another thread so that we can stop waiting if it hangs.
+    // The thread isn't joined, since grandchildren may keep the pipes open after a kill.
+    let (sender, receiver) = channel::bounded(1);
+    thread::spawn(move || {
+        // If the receiver is gone, we already timed out.
+        let _ = sender.send(communicate(stdin, stdout, &input));
+    });
+
+    let Ok(res) = receiver.recv_timeout(timeout) else {
+        let _ = child.kill();
+        let _ = child.wait();
+        bail!("child process did not finish within {timeout:?} and was killed");
+    };
+    let output = res?;
 
     let status = child.wait().context("failed to wait for child process")?;
 
@@ -119,11 +141,53 @@ fn pipe(command: &mut process::Command, input: &str) -> anyhow::Result<String> {
     Ok(output)
 }
 
+fn communicate(
+    mut stdin: process::ChildStdin,
+    mut stdout: process::ChildStdout,
+    input: &str,
+) -> anyhow::Result<String> {
+    stdin
+        .write_all(input.as_bytes())
+        .context("failed to write to child process' stdin")?;
+    drop(stdin);
+
+    let mut output = String::new();
+    stdout
+        .read_to_string(&mut output)
+        .context("failed to read from child process' stdout")?;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn below_threshold() {
+        let src = "<p>  a  </p>";
+        let mut s = src.to_owned();
+        let options = Options {
+            threshold: 256,
+            timeout: Duration::from_secs(1),
+        };
+        minify(options, FileType::Html, &mut s);
+        assert_eq!(s, src);
+    }
+
+    use super::minify;
+    use super::FileType;
+    use super::Options;
+    use std::time::Duration;
+}
+
 use crate::util::asset;
 use crate::util::asset::Asset;
 use crate::util::log_errors;
+use anyhow::bail;
 use anyhow::ensure;
 use anyhow::Context as _;
+use crossbeam::channel;
 use std::io::Read as _;
 use std::io::Write as _;
+use std::path::Path;
 use std::process;
+use std::thread;
+use std::time::Duration;
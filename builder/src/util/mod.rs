@@ -3,6 +3,7 @@
 pub(crate) mod asset;
 pub(crate) mod markdown;
 pub(crate) mod minify;
+pub(crate) mod precompress;
 pub(crate) mod push_str;
 pub(crate) mod serde;
 
@@ -57,15 +58,134 @@ impl From<anyhow::Error> for ErrorPage {
     }
 }
 
-pub(crate) fn write_file<P: AsRef<Path>, D: AsRef<[u8]>>(path: P, data: D) -> anyhow::Result<()> {
+/// Logs what was being done if the thread panics while this guard is alive,
+/// since unlike errors, panics can't have context attached to them.
+pub(crate) struct PanicContext<D: Display>(pub(crate) D);
+
+impl<D: Display> Drop for PanicContext<D> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            log::error!("panicked while {}", self.0);
+        }
+    }
+}
+
+/// The number of errors logged so far, so that strict builds can fail if there were any.
+static ERRORS: AtomicUsize = AtomicUsize::new(0);
+
+pub(crate) fn error_count() -> usize {
+    ERRORS.load(atomic::Ordering::Relaxed)
+}
+
+/// Install the global logger, configured through `RUST_LOG`,
+/// wrapped so that every logged error is counted.
+pub(crate) fn init_logger() {
+    struct Counting<L>(L);
+
+    impl<L: log::Log> log::Log for Counting<L> {
+        fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+            self.0.enabled(metadata)
+        }
+        fn log(&self, record: &log::Record<'_>) {
+            if record.level() == log::Level::Error {
+                ERRORS.fetch_add(1, atomic::Ordering::Relaxed);
+            }
+            self.0.log(record);
+        }
+        fn flush(&self) {
+            self.0.flush();
+        }
+    }
+
+    let mut builder = pretty_env_logger::formatted_builder();
+    if let Ok(filters) = env::var("RUST_LOG") {
+        builder.parse_filters(&filters);
+    }
+    let logger = builder.build();
+    // Errors are always counted, even if they wouldn't be shown.
+    log::set_max_level(logger.filter().max(log::LevelFilter::Error));
+    log::set_boxed_logger(Box::new(Counting(logger))).expect("logger already initialized");
+}
+
+pub(crate) fn write_file<P: AsRef<Path>, D: AsRef<[u8]>>(
+    config: &Config,
+    path: P,
+    data: D,
+) -> anyhow::Result<()> {
     let path = path.as_ref();
+    if config.dry_run {
+        log::info!("dry run: would write `{}`", path.display());
+        return Ok(());
+    }
     make_parents(path)?;
+    let data = data.as_ref();
     fs::write(path, data)
         .with_context(|| format!("couldn't write asset to `{}`", path.display()))?;
+    precompress::write(&config.precompress, path, data)?;
 
     Ok(())
 }
 
+pub(crate) fn copy_file<P: AsRef<Path>, Q: AsRef<Path>>(
+    config: &Config,
+    from: P,
+    to: Q,
+) -> anyhow::Result<()> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    if config.dry_run {
+        log::info!(
+            "dry run: would copy `{}` to `{}`",
+            from.display(),
+            to.display()
+        );
+        return Ok(());
+    }
+    make_parents(to)?;
+    fs::copy(from, to)
+        .with_context(|| format!("failed to copy {} to {}", from.display(), to.display()))?;
+    Ok(())
+}
+
+/// Remove a file written by an earlier build, if it exists.
+pub(crate) fn remove_file<P: AsRef<Path>>(config: &Config, path: P) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    if config.dry_run {
+        log::info!("dry run: would remove `{}`", path.display());
+        return Ok(());
+    }
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("failed to remove `{}`", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Remove the files directly in `dir` whose names satisfy `stale`, along with their compressed
+/// copies, to clean up after earlier builds. Nothing happens if `dir` doesn't exist.
+pub(crate) fn remove_stale_files(
+    config: &Config,
+    dir: &Path,
+    stale: impl Fn(&str) -> bool,
+) -> anyhow::Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).with_context(|| format!("failed to read dir `{}`", dir.display())),
+    };
+    for entry in entries {
+        let entry = entry.with_context(|| format!("failed to read dir `{}`", dir.display()))?;
+        let name = entry.file_name();
+        if name
+            .to_str()
+            .is_some_and(|name| stale(precompress::original_name(name)))
+        {
+            remove_file(config, entry.path())?;
+        }
+    }
+    Ok(())
+}
+
 pub(crate) fn make_parents<P: AsRef<Path>>(path: P) -> anyhow::Result<()> {
     if let Some(parent) = path.as_ref().parent() {
         fs::create_dir_all(parent)
@@ -118,6 +238,55 @@ pub(crate) mod precision_date {
                 PrecisionDate::Day(date) => u32::try_from(date.year()).unwrap(),
             }
         }
+
+        /// Display the date in a human-readable form, like `March 2021` or `5 March 2021`,
+        /// as opposed to the ISO 8601 form used by `Display`.
+        pub fn human(self) -> Human {
+            Human(self)
+        }
+
+        /// Whether every day this date could refer to is before every day `other` could,
+        /// so that for example `2021-03` is before `2021-04-01` but not before `2021`.
+        pub fn is_before(self, other: Self) -> bool {
+            self.days().1 < other.days().0
+        }
+
+        /// The first day the date could refer to, for ordering dates of mixed precision.
+        pub fn first_day(self) -> NaiveDate {
+            self.days().0
+        }
+
+        /// The first and last days the date could refer to.
+        fn days(self) -> (NaiveDate, NaiveDate) {
+            let ymd = |year: u32, month, day| {
+                NaiveDate::from_ymd_opt(i32::try_from(year).unwrap(), month, day).unwrap()
+            };
+            match self {
+                PrecisionDate::Year(year) => (ymd(year, 1, 1), ymd(year, 12, 31)),
+                PrecisionDate::Month(year, month) => {
+                    let month = month.number_from_month();
+                    let last = if month == 12 {
+                        ymd(year, 12, 31)
+                    } else {
+                        ymd(year, month + 1, 1).pred_opt().unwrap()
+                    };
+                    (ymd(year, month, 1), last)
+                }
+                PrecisionDate::Day(date) => (date, date),
+            }
+        }
+    }
+
+    pub(crate) struct Human(PrecisionDate);
+
+    impl Display for Human {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            match self.0 {
+                PrecisionDate::Year(year) => write!(f, "{year}"),
+                PrecisionDate::Month(year, month) => write!(f, "{} {year}", month.name()),
+                PrecisionDate::Day(date) => write!(f, "{}", date.format("%-d %B %Y")),
+            }
+        }
     }
 
     impl Display for PrecisionDate {
@@ -184,6 +353,22 @@ pub(crate) mod precision_date {
         }
     }
 
+    #[cfg(test)]
+    mod tests {
+        #[test]
+        fn is_before() {
+            let date = |s: &str| s.parse::<PrecisionDate>().ok().unwrap();
+            assert!(date("2021-03").is_before(date("2021-04-01")));
+            assert!(date("2021-12").is_before(date("2022")));
+            assert!(!date("2021-03").is_before(date("2021")));
+            assert!(!date("2021").is_before(date("2021-12-31")));
+            assert!(!date("2021-02-28").is_before(date("2021-02-28")));
+            assert!(!date("2022").is_before(date("2021-03")));
+        }
+
+        use super::PrecisionDate;
+    }
+
     use chrono::Datelike;
     use chrono::Month;
     use chrono::NaiveDate;
@@ -195,7 +380,14 @@ pub(crate) mod precision_date {
 }
 
 use self::push_str::push;
+use crate::config::Config;
 use anyhow::Context as _;
 use std::borrow::Borrow;
+use std::env;
+use std::fmt::Display;
 use std::fs;
+use std::io;
 use std::path::Path;
+use std::sync::atomic;
+use std::sync::atomic::AtomicUsize;
+use std::thread;
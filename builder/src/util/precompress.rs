@@ -0,0 +1,118 @@
+//! Compressed copies of output files, such as `index.html.gz`,
+//! for static hosts that serve them in place of the originals to clients that support it.
+
+#[derive(Hash, clap::Args)]
+pub(crate) struct Options {
+    /// Extension of output files to also write compressed copies of, such as `html`.
+    /// May be given multiple times. Nothing is compressed by default.
+    #[clap(long = "precompress")]
+    extensions: Vec<String>,
+
+    /// Format to write compressed copies in. May be given multiple times.
+    #[clap(long = "precompress-format", value_enum, default_values = ["gzip", "brotli"])]
+    formats: Vec<Format>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub(crate) enum Format {
+    Gzip,
+    Brotli,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Gzip => "gz",
+            Self::Brotli => "br",
+        }
+    }
+
+    fn compress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut compressed = Vec::new();
+        match self {
+            Self::Gzip => {
+                let mut encoder = GzEncoder::new(&mut compressed, Compression::best());
+                encoder.write_all(data)?;
+                encoder.finish()?;
+            }
+            Self::Brotli => {
+                let params = BrotliEncoderParams::default();
+                brotli::BrotliCompress(&mut &*data, &mut compressed, &params)?;
+            }
+        }
+        Ok(compressed)
+    }
+}
+
+/// Write compressed copies of a file that has just been written, if its extension is enabled.
+pub(crate) fn write(options: &Options, path: &Path, data: &[u8]) -> anyhow::Result<()> {
+    let Some(extension) = path.extension().and_then(OsStr::to_str) else {
+        return Ok(());
+    };
+    if !options
+        .extensions
+        .iter()
+        .any(|enabled| enabled.eq_ignore_ascii_case(extension))
+    {
+        return Ok(());
+    }
+
+    for &format in &options.formats {
+        let mut compressed_path = path.as_os_str().to_owned();
+        compressed_path.push(".");
+        compressed_path.push(format.extension());
+        let compressed_path = PathBuf::from(compressed_path);
+
+        let compressed = format
+            .compress(data)
+            .with_context(|| format!("failed to compress `{}`", path.display()))?;
+        fs::write(&compressed_path, compressed)
+            .with_context(|| format!("couldn't write `{}`", compressed_path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// The name of the file that the file with the given name is a compressed copy of,
+/// or the name itself if it isn't one.
+pub(crate) fn original_name(name: &str) -> &str {
+    [Format::Gzip, Format::Brotli]
+        .into_iter()
+        .find_map(|format| name.strip_suffix(format.extension())?.strip_suffix('.'))
+        .unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn round_trip() {
+        let data = b"<!DOCTYPE html><p>hello hello hello</p>".repeat(10);
+
+        let mut decompressed = Vec::new();
+        let compressed = Format::Gzip.compress(&data).unwrap();
+        GzDecoder::new(&*compressed)
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, data);
+
+        decompressed.clear();
+        let compressed = Format::Brotli.compress(&data).unwrap();
+        brotli::BrotliDecompress(&mut &*compressed, &mut decompressed).unwrap();
+        assert_eq!(decompressed, data);
+    }
+
+    use super::Format;
+    use flate2::read::GzDecoder;
+    use std::io::Read as _;
+}
+
+use anyhow::Context as _;
+use brotli::enc::BrotliEncoderParams;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
@@ -54,8 +54,11 @@ pub(crate) fn escape_html(buf: &mut impl PushStr, s: &str) {
     pulldown_cmark::escape::escape_html(buf.writer(), s).unwrap();
 }
 
+/// Percent-encode the characters in a URL that aren't valid in one, such as spaces,
+/// leaving existing percent-encodings and reserved characters alone,
+/// and HTML-escape the result so it can be placed in an attribute.
 pub(crate) fn escape_href(buf: &mut impl PushStr, s: &str) {
-    pulldown_cmark::escape::escape_html(buf.writer(), s).unwrap();
+    pulldown_cmark::escape::escape_href(buf.writer(), s).unwrap();
 }
 
 use std::fmt;
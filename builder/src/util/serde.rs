@@ -37,7 +37,7 @@ mod literal_str {
         }
     }
 
-    impl<'de> de::Visitor<'de> for LiteralStr<'_> {
+    impl de::Visitor<'_> for LiteralStr<'_> {
         type Value = ();
         fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
             write!(f, "the string `{}`", self.0)
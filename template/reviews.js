@@ -21,76 +21,59 @@ for (const container of document.getElementsByClassName("page-controls")) {
 	container.append(first, prev, middle, next, last);
 	page_controls.push({ first, last, prev, next, middle });
 }
-const score_header = document.getElementById("score-header");
-const [filter_input, filter_clear_button] = document.getElementById("filter").children;
-
-const UNSORTED = "↕";
-const ASCENDING = "↑";
-const DESCENDING = "↓";
-const SORT_BY_SCORE = "Score";
-const SORT_BY_DATE = "Date";
+const [filter_input, filter_clear_button, type_select, sort_select] = document.getElementById("filter").children;
+
+const compare_text = (a, b) => a.localeCompare(b);
+const compare_numbers = (a, b) => a - b;
+const released = row => row.dataset.released;
+const score = row => row.dataset.score === "" ? null : parseInt(row.dataset.score);
+const reviewed = row => row.dataset.reviewed === "" ? null : row.dataset.reviewed;
+const title = row => row.getElementsByTagName("strong")[0].textContent;
+
+// One sort for every option of the sort selector, in the same order.
+// The first is the order the rows are generated in, newest release first, so needs no sorting.
+// Rows without a key, such as those not yet reviewed, always go last.
 const SORTS = [
-	{ by: SORT_BY_SCORE, direction: UNSORTED },
-	{ by: SORT_BY_SCORE, direction: DESCENDING },
-	{ by: SORT_BY_SCORE, direction: ASCENDING },
-	{ by: SORT_BY_DATE, direction: DESCENDING },
-	{ by: SORT_BY_DATE, direction: ASCENDING },
+	null,
+	{ key: released, compare: compare_text, direction: 1 },
+	{ key: score, compare: compare_numbers, direction: -1 },
+	{ key: score, compare: compare_numbers, direction: 1 },
+	{ key: reviewed, compare: compare_text, direction: -1 },
+	{ key: reviewed, compare: compare_text, direction: 1 },
+	{ key: title, compare: compare_text, direction: 1 },
 ];
 
-let current_sort;
-let current_filter;
 let pages;
 
-function set_sort_and_filter(new_sort, new_filter) {
-	current_sort = new_sort;
-	current_filter = new_filter;
-	filter_input.value = new_filter;
+function update_rows() {
+	const filter = filter_input.value;
 	history.replaceState(null, "", location.origin + location.pathname + (
-		new_filter === "" ? "" : `?q=${new_filter}`
+		filter === "" ? "" : `?q=${filter}`
 	));
 
-	let rows;
-	if (new_filter !== null) {
-		const parts = new_filter.toLowerCase().split(" ");
-		rows = original_rows.filter(row => {
-			const row_content = row.content.firstElementChild.textContent.toLowerCase();
-			return parts.every(part => row_content.includes(part));
-		});
-	} else {
-		rows = [...original_rows];
-	}
-
-	const sort_data = SORTS[new_sort];
-
-	let multiplier;
-	switch (sort_data.direction) {
-		case UNSORTED: multiplier = 0; break;
-		case ASCENDING: multiplier = 1; break;
-		case DESCENDING: multiplier = -1; break;
-	}
-
-	let row_val;
-	switch (sort_data.by) {
-		case SORT_BY_DATE: {
-			row_val = row => {
-				const elem = row.content.firstElementChild.getElementsByTagName("time")[0];
-				return (elem && multiplier * Date.parse(elem.dateTime)) ?? Infinity;
-			};
-			break;
-		}
-		case SORT_BY_SCORE: {
-			row_val = row => {
-				const elem = row.content.firstElementChild.getElementsByClassName("score")[0];
-				return (elem && multiplier * parseFloat(elem.textContent)) ?? Infinity;
-			};
-			break;
+	const parts = filter.toLowerCase().split(" ");
+	const type = type_select.value;
+	const rows = original_rows.filter(row => {
+		const row_element = row.content.firstElementChild;
+		if (type !== "" && row_element.dataset.type !== type) {
+			return false;
 		}
-	}
+		const row_content = row_element.textContent.toLowerCase();
+		return parts.every(part => row_content.includes(part));
+	});
 
-	if (multiplier !== 0) {
-		rows.sort((row_a, row_b) => row_val(row_a) - row_val(row_b));
+	const sort = SORTS[sort_select.selectedIndex];
+	if (sort !== null) {
+		const keys = new Map(rows.map(row => [row, sort.key(row.content.firstElementChild)]));
+		rows.sort((row_a, row_b) => {
+			const a = keys.get(row_a);
+			const b = keys.get(row_b);
+			if (a === null || b === null) {
+				return (a === null) - (b === null);
+			}
+			return sort.direction * sort.compare(a, b);
+		});
 	}
-	score_header.textContent = `${sort_data.by} ${sort_data.direction}`;
 
 	pages = [];
 	for (let i = 0; i < rows.length; i += page_size) {
@@ -136,10 +119,6 @@ function set_shown_page(new_page) {
 	current_page = new_page;
 }
 
-score_header.parentElement.addEventListener("click", () => {
-	set_sort_and_filter((current_sort + 1) % SORTS.length, current_filter);
-});
-
 for (const { first, last, prev, next, middle } of page_controls) {
 	first.addEventListener("click", () => set_shown_page(0));
 	last.addEventListener("click", () => set_shown_page(pages.length - 1));
@@ -182,7 +161,13 @@ for (const { first, last, prev, next, middle } of page_controls) {
 	});
 }
 
-filter_input.addEventListener("input", () => set_sort_and_filter(current_sort, filter_input.value));
-filter_clear_button.addEventListener("click", () => set_sort_and_filter(current_sort, ""));
+filter_input.addEventListener("input", () => update_rows());
+filter_clear_button.addEventListener("click", () => {
+	filter_input.value = "";
+	update_rows();
+});
+type_select.addEventListener("change", () => update_rows());
+sort_select.addEventListener("change", () => update_rows());
 
-set_sort_and_filter(0, (new URLSearchParams(location.search)).get("q") || filter_input.value);
+filter_input.value = (new URLSearchParams(location.search)).get("q") || filter_input.value;
+update_rows();